@@ -0,0 +1,119 @@
+use crate::debruijn::*;
+use crate::term::*;
+use std::hash::{Hash, Hasher};
+
+/// Checks whether two terms are α-equivalent, i.e. equal up to the names of
+/// bound variables. Free variables must have the same names.
+///
+/// Examples:
+///   `λx. x` and `λy. y` are α-equivalent.
+///   `λx. y` and `λz. y` are α-equivalent, but `λx. y` and `λx. z` are not.
+///   `λx. λy. x` and `λy. λx. x` are not.
+pub fn alpha_eq(t1: &Term, t2: &Term) -> bool {
+    // the free variables are listed in order of their first occurrence,
+    // so equal de Bruijn terms with equal lists name the same variables
+    DbTerm::from_term(t1) == DbTerm::from_term(t2)
+}
+
+/// A term that is compared and hashed up to α-equivalence,
+/// e.g. to collect terms in a `HashSet` without duplicates modulo renaming.
+#[derive(Debug, Clone)]
+pub struct Alpha(pub Term);
+
+impl PartialEq for Alpha {
+    fn eq(&self, other: &Self) -> bool {
+        alpha_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Alpha {}
+
+/// Hashes the de Bruijn representation, which is the same for all α-equivalent terms.
+impl Hash for Alpha {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        DbTerm::from_term(&self.0).hash(state);
+    }
+}
+
+/// Asserts that two terms are α-equivalent, printing both terms otherwise.
+///
+/// Use this instead of `assert_eq!` when the names of bound variables, such
+/// as the fresh names chosen by substitution, are not part of the expected result.
+#[macro_export]
+macro_rules! assert_alpha_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !$crate::alpha::alpha_eq(left, right) {
+                    panic!(
+                        "assertion failed: terms are not α-equivalent\n  left: {}\n right: {}",
+                        left, right
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !$crate::alpha::alpha_eq(left, right) {
+                    panic!(
+                        "assertion failed: terms are not α-equivalent\n  left: {}\n right: {}\n{}",
+                        left,
+                        right,
+                        format_args!($($arg)+)
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_alpha_eq() {
+        assert!(alpha_eq(&abs("x", var("x")), &abs("y", var("y"))));
+        assert!(alpha_eq(&abs("x", var("y")), &abs("z", var("y"))));
+        assert!(!alpha_eq(&abs("x", var("y")), &abs("x", var("z"))));
+        assert!(!alpha_eq(&abs("x", abs("y", var("x"))), &abs("y", abs("x", var("x")))));
+        assert!(!alpha_eq(&app(var("y"), var("z")), &app(var("z"), var("y"))));
+        // a bound variable is never equal to a free one
+        assert!(!alpha_eq(&abs("x", var("x")), &abs("y", var("x"))));
+    }
+
+    #[test]
+    fn test_alpha_eq_with_shadowing() {
+        let t1 = abs("x", abs("x", app(var("x"), var("y"))));
+        let t2 = abs("a", abs("b", app(var("b"), var("y"))));
+        assert!(alpha_eq(&t1, &t2));
+        let t3 = abs("a", abs("b", app(var("a"), var("y"))));
+        assert!(!alpha_eq(&t1, &t3));
+    }
+
+    #[test]
+    fn test_alpha_hash() {
+        let terms: HashSet<Alpha> = [
+            abs("x", var("x")),
+            abs("y", var("y")),
+            abs("x", abs("y", var("x"))),
+            abs("a", abs("b", var("a"))),
+            abs("x", var("z")),
+        ]
+        .into_iter()
+        .map(Alpha)
+        .collect();
+        assert_eq!(terms.len(), 3);
+        assert!(terms.contains(&Alpha(abs("f", var("f")))));
+    }
+
+    #[test]
+    fn test_assert_alpha_eq() {
+        assert_alpha_eq!(abs("x", app(var("x"), var("y"))), abs("z", app(var("z"), var("y"))));
+        let result = std::panic::catch_unwind(|| assert_alpha_eq!(var("x"), var("y")));
+        assert!(result.is_err());
+    }
+}
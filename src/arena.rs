@@ -0,0 +1,539 @@
+use crate::term::*;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// A term stored in a [`TermArena`]. Ids are only meaningful for the arena
+/// that created them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TermId(usize);
+
+/// A node of a term in a [`TermArena`], referring to its subterms by id.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Var(Symbol),
+    Abs(Symbol, TermId),
+    App(TermId, TermId),
+}
+
+/// A node with its free variables, sorted and without duplicates.
+#[derive(Debug, Clone)]
+struct Entry {
+    node: Node,
+    free: Rc<[Symbol]>,
+}
+
+/// Hash-consed storage for terms.
+///
+/// Every structurally distinct term is stored exactly once, so subterms are
+/// shared instead of copied and two ids are equal iff they denote the same
+/// term, which makes equality an O(1) comparison. Substitution inserts the
+/// replacement by reference, so duplicating an argument costs nothing.
+///
+/// Nodes are never removed; an arena is meant to live as long as the
+/// evaluation of one term.
+///
+/// Example:
+///   `x x` is stored as two nodes, `Var("x")` and `App(0, 0)`.
+#[derive(Debug, Clone, Default)]
+pub struct TermArena {
+    entries: Vec<Entry>,
+    ids: HashMap<Node, TermId>,
+    /// All names occurring in the arena, to create fresh names.
+    names: HashSet<Symbol>,
+    counters: HashMap<Symbol, usize>,
+}
+
+impl TermArena {
+    /// Creates an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of distinct terms stored.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The node of the term with the given id.
+    pub fn node(&self, id: TermId) -> &Node {
+        &self.entries[id.0].node
+    }
+
+    pub fn var(&mut self, name: &str) -> TermId {
+        self.intern(Node::Var(Symbol::intern(name)))
+    }
+
+    pub fn abs(&mut self, param: &str, body: TermId) -> TermId {
+        self.intern(Node::Abs(Symbol::intern(param), body))
+    }
+
+    pub fn app(&mut self, t1: TermId, t2: TermId) -> TermId {
+        self.intern(Node::App(t1, t2))
+    }
+
+    /// Checks whether `name` occurs free in the term, in O(log n).
+    pub fn is_free_in(&self, name: impl Into<Symbol>, id: TermId) -> bool {
+        self.entries[id.0].free.binary_search(&name.into()).is_ok()
+    }
+
+    /// Stores a term, sharing all identical subterms.
+    pub fn insert(&mut self, term: &Term) -> TermId {
+        fold(term, &mut Insert(self))
+    }
+
+    /// Converts a stored term back to a tree.
+    ///
+    /// Shared subterms are copied at every occurrence, so the result can be
+    /// exponentially larger than the part of the arena it was built from,
+    /// see [`TermArena::tree_size`].
+    pub fn to_term(&self, id: TermId) -> Term {
+        fold(id, &mut Tree(self))
+    }
+
+    /// The number of nodes of the term as a tree, i.e. the [`Term::size`] of
+    /// [`TermArena::to_term`], saturating at `usize::MAX`.
+    pub fn tree_size(&self, id: TermId) -> usize {
+        let mut sizes: HashMap<TermId, usize> = HashMap::new();
+        // a term is visited twice: first to push its children, then to add up their sizes
+        let mut stack = vec![(id, false)];
+        while let Some((id, children_done)) = stack.pop() {
+            if sizes.contains_key(&id) {
+                continue;
+            }
+            match (self.node(id), children_done) {
+                (Node::Var(_), _) => {
+                    sizes.insert(id, 1);
+                }
+                (Node::Abs(_, body), true) => {
+                    sizes.insert(id, sizes[body].saturating_add(1));
+                }
+                (Node::App(t1, t2), true) => {
+                    sizes.insert(id, sizes[t1].saturating_add(sizes[t2]).saturating_add(1));
+                }
+                (Node::Abs(_, body), false) => {
+                    stack.push((id, true));
+                    stack.push((*body, false));
+                }
+                (Node::App(t1, t2), false) => {
+                    stack.push((id, true));
+                    stack.push((*t2, false));
+                    stack.push((*t1, false));
+                }
+            }
+        }
+        sizes[&id]
+    }
+
+    /// Capture-avoiding substitution of `replacement` for the free occurrences
+    /// of `var` in the term, like [`crate::eval::substitute`].
+    ///
+    /// Subterms in which `var` does not occur free are kept as they are,
+    /// and each shared subterm is substituted in only once.
+    pub fn substitute(&mut self, id: TermId, var: impl Into<Symbol>, replacement: TermId) -> TermId {
+        /// A substitution of `replacement` for `var`. Renaming a parameter
+        /// starts another one in the same worklist.
+        struct Job {
+            var: Symbol,
+            replacement: TermId,
+            done: HashMap<TermId, TermId>,
+        }
+
+        enum Task {
+            Visit(usize, TermId),
+            /// Substitutes in the body of an abstraction whose parameter was
+            /// renamed to the given name, once the renaming is done.
+            Renamed(usize, TermId, Symbol),
+            BuildAbs(usize, TermId, Symbol),
+            BuildApp(usize, TermId),
+        }
+
+        let mut jobs = vec![Job {
+            var: var.into(),
+            replacement,
+            done: HashMap::new(),
+        }];
+        let mut tasks = vec![Task::Visit(0, id)];
+        let mut results: Vec<TermId> = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(job, id) => {
+                    let Job { var, replacement, ref done } = jobs[job];
+                    if !self.is_free_in(var, id) {
+                        results.push(id);
+                        continue;
+                    }
+                    if let Some(&result) = done.get(&id) {
+                        results.push(result);
+                        continue;
+                    }
+                    match self.node(id).clone() {
+                        // `var` is free, so this is an occurrence of it
+                        Node::Var(_) => results.push(replacement),
+                        Node::Abs(param, body) if self.is_free_in(param, replacement) => {
+                            // rename the parameter so it does not capture a free variable
+                            // of the replacement; fresh names occur nowhere in the arena
+                            let fresh = self.fresh_name(param);
+                            let renamed = self.intern(Node::Var(fresh));
+                            jobs.push(Job {
+                                var: param,
+                                replacement: renamed,
+                                done: HashMap::new(),
+                            });
+                            tasks.push(Task::Renamed(job, id, fresh));
+                            tasks.push(Task::Visit(jobs.len() - 1, body));
+                        }
+                        Node::Abs(param, body) => {
+                            tasks.push(Task::BuildAbs(job, id, param));
+                            tasks.push(Task::Visit(job, body));
+                        }
+                        Node::App(t1, t2) => {
+                            tasks.push(Task::BuildApp(job, id));
+                            tasks.push(Task::Visit(job, t2));
+                            tasks.push(Task::Visit(job, t1));
+                        }
+                    }
+                }
+                Task::Renamed(job, original, fresh) => {
+                    let body = results.pop().expect("missing body");
+                    tasks.push(Task::BuildAbs(job, original, fresh));
+                    tasks.push(Task::Visit(job, body));
+                }
+                Task::BuildAbs(job, original, param) => {
+                    let body = results.pop().expect("missing body");
+                    let result = self.intern(Node::Abs(param, body));
+                    jobs[job].done.insert(original, result);
+                    results.push(result);
+                }
+                Task::BuildApp(job, original) => {
+                    let t2 = results.pop().expect("missing argument");
+                    let t1 = results.pop().expect("missing function");
+                    let result = self.app(t1, t2);
+                    jobs[job].done.insert(original, result);
+                    results.push(result);
+                }
+            }
+        }
+        results.pop().expect("missing result")
+    }
+
+    /// Computes the β-normal form of a term, performing at most `max_steps`
+    /// contractions. Returns `None` if the budget is exhausted.
+    ///
+    /// Reduction follows the normal order strategy, so the normal form is found
+    /// whenever it exists, but each shared subterm is normalized only once.
+    pub fn normalize(&mut self, id: TermId, max_steps: usize) -> Option<TermId> {
+        enum Task {
+            Visit(TermId),
+            BuildAbs(TermId, Symbol),
+            /// Applies a variable to the given number of normalized arguments.
+            BuildSpine(TermId, TermId, usize),
+        }
+
+        let mut steps = 0;
+        let mut normal_forms: HashMap<TermId, TermId> = HashMap::new();
+        let mut tasks = vec![Task::Visit(id)];
+        let mut results: Vec<TermId> = Vec::new();
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(id) if normal_forms.contains_key(&id) => results.push(normal_forms[&id]),
+                Task::Visit(id) => {
+                    let (head, args) = self.weak_head_normalize(id, &mut steps, max_steps)?;
+                    match self.node(head).clone() {
+                        Node::Abs(param, body) => {
+                            tasks.push(Task::BuildAbs(id, param));
+                            tasks.push(Task::Visit(body));
+                        }
+                        _ => {
+                            tasks.push(Task::BuildSpine(id, head, args.len()));
+                            tasks.extend(args.into_iter().rev().map(Task::Visit));
+                        }
+                    }
+                }
+                Task::BuildAbs(original, param) => {
+                    let body = results.pop().expect("missing body");
+                    let result = self.intern(Node::Abs(param, body));
+                    normal_forms.insert(original, result);
+                    results.push(result);
+                }
+                Task::BuildSpine(original, head, count) => {
+                    let args = results.split_off(results.len() - count);
+                    let result = args.into_iter().fold(head, |t, arg| self.app(t, arg));
+                    normal_forms.insert(original, result);
+                    results.push(result);
+                }
+            }
+        }
+        results.pop()
+    }
+
+    /// Contracts head redexes until the term is an abstraction or a variable
+    /// applied to arguments. Returns the head and the arguments.
+    fn weak_head_normalize(
+        &mut self,
+        id: TermId,
+        steps: &mut usize,
+        max_steps: usize,
+    ) -> Option<(TermId, Vec<TermId>)> {
+        // arguments of the spine, the first one last
+        let mut args = Vec::new();
+        let mut head = id;
+        loop {
+            match self.node(head).clone() {
+                Node::App(t1, t2) => {
+                    args.push(t2);
+                    head = t1;
+                }
+                Node::Abs(param, body) if !args.is_empty() => {
+                    if *steps >= max_steps {
+                        return None;
+                    }
+                    *steps += 1;
+                    let arg = args.pop().expect("checked above");
+                    head = self.substitute(body, param, arg);
+                }
+                _ => break,
+            }
+        }
+        args.reverse();
+        Some((head, args))
+    }
+
+    /// Returns the id of the node, adding it if it is not stored yet.
+    fn intern(&mut self, node: Node) -> TermId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let free = match &node {
+            Node::Var(x) => {
+                self.names.insert(*x);
+                Rc::from([*x])
+            }
+            Node::Abs(param, body) => {
+                self.names.insert(*param);
+                let free = &self.entries[body.0].free;
+                if free.binary_search(param).is_ok() {
+                    free.iter().filter(|x| *x != param).copied().collect()
+                } else {
+                    free.clone()
+                }
+            }
+            Node::App(t1, t2) => union(&self.entries[t1.0].free, &self.entries[t2.0].free),
+        };
+        let id = TermId(self.entries.len());
+        self.entries.push(Entry {
+            node: node.clone(),
+            free,
+        });
+        self.ids.insert(node, id);
+        id
+    }
+
+    /// A name of the form `x_1`, `x_2`, ... that does not occur in the arena yet.
+    /// Unlike the names made by [`crate::eval::substitute`], these are never reused, so
+    /// every renaming interns one more name for the rest of the program.
+    fn fresh_name(&mut self, base: Symbol) -> Symbol {
+        let counter = self.counters.entry(base).or_insert(0);
+        loop {
+            *counter += 1;
+            let candidate = base.numbered(*counter);
+            if self.names.insert(candidate) {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// Merges two sorted lists of names.
+fn union(a: &Rc<[Symbol]>, b: &Rc<[Symbol]>) -> Rc<[Symbol]> {
+    if b.is_empty() || a == b {
+        return a.clone();
+    }
+    if a.is_empty() {
+        return b.clone();
+    }
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                merged.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                merged.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                merged.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    merged.extend_from_slice(&a[i..]);
+    merged.extend_from_slice(&b[j..]);
+    merged.into()
+}
+
+/// Normalizes a term in a [`TermArena`], so that duplicated arguments are
+/// shared rather than copied. Returns `None` if `max_steps` contractions
+/// do not suffice.
+pub fn eval_shared(term: &Term, max_steps: usize) -> Option<Term> {
+    let mut arena = TermArena::new();
+    let id = arena.insert(term);
+    let normal_form = arena.normalize(id, max_steps)?;
+    Some(arena.to_term(normal_form))
+}
+
+/// Stores a term bottom-up, see [`TermArena::insert`].
+struct Insert<'a>(&'a mut TermArena);
+
+impl<'a> Fold<&'a Term> for Insert<'_> {
+    type Binder = Symbol;
+    type Output = TermId;
+
+    fn visit(&mut self, term: &'a Term) -> Visit<&'a Term, Symbol, TermId> {
+        match term {
+            Term::Var(x) => Visit::Done(self.0.intern(Node::Var(*x))),
+            Term::Abs(param, body) => Visit::Abs(*param, body),
+            Term::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+
+    fn abs(&mut self, param: Symbol, body: TermId) -> TermId {
+        self.0.intern(Node::Abs(param, body))
+    }
+
+    fn app(&mut self, t1: TermId, t2: TermId) -> TermId {
+        self.0.intern(Node::App(t1, t2))
+    }
+}
+
+/// Converts a stored term to a tree, see [`TermArena::to_term`].
+struct Tree<'a>(&'a TermArena);
+
+impl Fold<TermId> for Tree<'_> {
+    type Binder = Symbol;
+    type Output = Term;
+
+    fn visit(&mut self, id: TermId) -> Visit<TermId, Symbol, Term> {
+        match self.0.node(id) {
+            Node::Var(x) => Visit::Done(Term::Var(*x)),
+            Node::Abs(param, body) => Visit::Abs(*param, *body),
+            Node::App(t1, t2) => Visit::App(*t1, *t2),
+        }
+    }
+
+    fn abs(&mut self, param: Symbol, body: Term) -> Term {
+        Term::Abs(param, Box::new(body))
+    }
+
+    fn app(&mut self, t1: Term, t2: Term) -> Term {
+        Term::App(Box::new(t1), Box::new(t2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assert_alpha_eq;
+    use crate::eval::eval;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_identical_subterms_are_stored_once() {
+        let mut arena = TermArena::new();
+        let t1 = arena.insert(&abs("x", app(var("x"), var("x"))));
+        let t2 = arena.insert(&abs("x", app(var("x"), var("x"))));
+        assert_eq!(t1, t2);
+        assert_eq!(arena.len(), 3);
+
+        // α-equivalent but differently named terms are distinct
+        let t3 = arena.insert(&abs("y", app(var("y"), var("y"))));
+        assert_ne!(t1, t3);
+    }
+
+    #[test]
+    fn test_insert_and_to_term_round_trip() {
+        let term = parse("λf x. f (f x) (λy. y x)").unwrap();
+        let mut arena = TermArena::new();
+        let id = arena.insert(&term);
+        assert_eq!(arena.to_term(id), term);
+        assert_eq!(arena.tree_size(id), term.size());
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let mut arena = TermArena::new();
+        let id = arena.insert(&abs("x", app(app(var("x"), var("y")), var("z"))));
+        assert!(arena.is_free_in("y", id));
+        assert!(arena.is_free_in("z", id));
+        assert!(!arena.is_free_in("x", id));
+    }
+
+    #[test]
+    fn test_substitute_avoids_capture() {
+        let mut arena = TermArena::new();
+        let term = arena.insert(&abs("x", app(var("y"), var("x"))));
+        let replacement = arena.var("x");
+        let result = arena.substitute(term, "y", replacement);
+        assert_alpha_eq!(arena.to_term(result), abs("z", app(var("x"), var("z"))));
+    }
+
+    #[test]
+    fn test_substitute_renames_deep_bodies() {
+        // λy. f (f (… (x y))), where the body under the renamed binder is deep
+        let mut body = app(var("x"), var("y"));
+        for _ in 0..100000 {
+            body = app(var("f"), body);
+        }
+        let mut arena = TermArena::new();
+        let term = arena.insert(&abs("y", body));
+        let replacement = arena.var("y");
+        let result = arena.substitute(term, "x", replacement);
+        assert!(arena.is_free_in("y", result));
+        assert!(!arena.is_free_in("x", result));
+        assert_eq!(arena.tree_size(result), arena.tree_size(term));
+    }
+
+    #[test]
+    fn test_normalize_agrees_with_eval() {
+        let sources = [
+            "(λx. x) ((λy. y) z)",
+            "(λx. λy. x) y",
+            "(λm n f x. m f (n f x)) (λf x. f x) (λf x. f (f x))",
+            "(λx. z) ((λx. x x) (λx. x x))",
+            "(λm n. n m) (λf x. f (f x)) (λf x. f (f (f x)))",
+        ];
+        for source in sources {
+            let term = parse(source).unwrap();
+            let result = eval_shared(&term, 1000).expect("normal form exists");
+            assert_alpha_eq!(result, eval(&term), "normalizing `{source}`");
+        }
+    }
+
+    #[test]
+    fn test_normalize_runs_out_of_fuel() {
+        let omega = parse("(λx. x x) (λx. x x)").unwrap();
+        assert_eq!(eval_shared(&omega, 100), None);
+    }
+
+    #[test]
+    fn test_duplicated_arguments_are_shared() {
+        // (λx. p x x) ((λx. p x x) (... y)) doubles the size of its argument
+        // at every level, which the arena stores in linear space
+        let mut term = var("y");
+        for _ in 0..64 {
+            term = app(abs("x", app(app(var("p"), var("x")), var("x"))), term);
+        }
+        let mut arena = TermArena::new();
+        let id = arena.insert(&term);
+        let normal_form = arena.normalize(id, 1000).unwrap();
+        assert_eq!(arena.tree_size(normal_form), usize::MAX);
+        assert!(arena.len() < 1000);
+    }
+}
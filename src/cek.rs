@@ -0,0 +1,145 @@
+//! A CEK machine for call-by-value evaluation.
+//!
+//! The machine state consists of the control, i.e. a term to evaluate in an
+//! environment or a value to return, and the continuation, an explicit stack
+//! of frames telling what to do with the value. Variables are looked up in
+//! the environment instead of substituting, and the native stack is never
+//! used, so arbitrarily deep computations can be run.
+
+use crate::debruijn::*;
+use crate::eval::*;
+use crate::machine::{self, Readback};
+use crate::term::*;
+
+type Frames<'a> = machine::Frames<Value<'a>>;
+
+/// The result of evaluating a term: an abstraction with the environment of
+/// its free variables, or a free variable applied to values.
+type Value<'a> = machine::Value<Closure<'a>>;
+
+#[derive(Clone)]
+struct Closure<'a>(Symbol, &'a DbTerm, Frames<'a>);
+
+/// What the machine does next.
+enum Control<'a> {
+    /// Evaluate a term in an environment.
+    Eval(&'a DbTerm, Frames<'a>),
+    /// Pass a value to the innermost frame of the continuation.
+    Return(Value<'a>),
+}
+
+/// A frame of the continuation.
+enum Frame<'a> {
+    /// Evaluate the argument of an application whose function is being evaluated.
+    Arg(&'a DbTerm, Frames<'a>),
+    /// Apply the evaluated function to the argument being evaluated.
+    Call(Value<'a>),
+}
+
+/// Evaluates a term with the CEK machine, see [`Backend::Cek`].
+///
+/// Supports [`Strategy::CallByValue`] only: the function and then the
+/// argument of an application are evaluated, never reducing under
+/// abstractions.
+pub fn eval_cek(term: &Term, strategy: Strategy, limits: Limits) -> Result<Term, EvalError> {
+    if strategy != Strategy::CallByValue {
+        return Err(EvalError::Unsupported {
+            backend: Backend::Cek,
+            strategy,
+        });
+    }
+
+    let (db, free) = DbTerm::from_term(term);
+    let mut control = Control::Eval(&db, Frames::default());
+    let mut frames = Vec::new();
+    let mut steps = 0;
+    let out_of_fuel = loop {
+        control = match control {
+            Control::Eval(term, env) => match term {
+                DbTerm::Var(index) => Control::Return(match env.get(*index) {
+                    Ok(value) => value.clone(),
+                    Err(depth) => Value::free(index - depth),
+                }),
+                DbTerm::Abs(hint, body) => Control::Return(Value::Closure(Closure(*hint, body, env))),
+                DbTerm::App(t1, t2) => {
+                    frames.push(Frame::Arg(t2, env.clone()));
+                    Control::Eval(t1, env)
+                }
+            },
+            Control::Return(value) => match frames.pop() {
+                None => {
+                    control = Control::Return(value);
+                    break false;
+                }
+                Some(Frame::Arg(t2, env)) => {
+                    frames.push(Frame::Call(value));
+                    Control::Eval(t2, env)
+                }
+                Some(Frame::Call(function @ Value::Closure(..))) if steps == limits.max_steps => {
+                    frames.push(Frame::Call(function));
+                    control = Control::Return(value);
+                    break true;
+                }
+                Some(Frame::Call(Value::Closure(Closure(_, body, env)))) => {
+                    steps += 1;
+                    Control::Eval(body, env.push(value))
+                }
+                Some(Frame::Call(Value::Neutral(neutral))) => {
+                    Control::Return(Value::apply_neutral(neutral, value))
+                }
+                Some(Frame::Call(Value::Level(_))) => unreachable!("levels are only used by the readback"),
+            },
+        };
+    };
+
+    // plug the control into the continuation, innermost frame first
+    let mut result = match control {
+        Control::Eval(term, env) => readback(Quote::Term(term, env)),
+        Control::Return(value) => readback(Quote::Value(value)),
+    };
+    while let Some(frame) = frames.pop() {
+        let (t1, t2) = match frame {
+            Frame::Arg(t2, env) => (result, readback(Quote::Term(t2, env))),
+            Frame::Call(function) => (readback(Quote::Value(function)), result),
+        };
+        result = DbTerm::App(Box::new(t1), Box::new(t2));
+    }
+    machine::outcome(result.to_term(&free), steps, out_of_fuel, limits)
+}
+
+/// Something to read back with [`readback`].
+enum Quote<'a> {
+    Term(&'a DbTerm, Frames<'a>),
+    Value(Value<'a>),
+}
+
+/// Turns a term in an environment or a value into a de Bruijn term by
+/// substituting the values of its variables, without evaluating anything.
+fn readback(quote: Quote) -> DbTerm {
+    let mut readback = Readback::new();
+    readback.push(quote, 0);
+    readback.run(|readback, quote, depth| match quote {
+        Quote::Term(term, env) => match term {
+            DbTerm::Var(index) => match env.get(*index) {
+                Ok(value) => readback.push(Quote::Value(value.clone()), depth),
+                Err(env_depth) => readback.free(index - env_depth, depth),
+            },
+            DbTerm::Abs(hint, body) => {
+                let env = env.push(Value::Level(depth));
+                readback.push_abs(*hint, Quote::Term(body, env), depth);
+            }
+            DbTerm::App(t1, t2) => {
+                readback.push_app(1, depth);
+                readback.push(Quote::Term(t2, env.clone()), depth);
+                readback.push(Quote::Term(t1, env), depth);
+            }
+        },
+        Quote::Value(value) => {
+            if let Some(Closure(hint, body, env)) = readback.value(value, depth, Quote::Value) {
+                let env = env.push(Value::Level(depth));
+                readback.push_abs(hint, Quote::Term(body, env), depth);
+            }
+        }
+    });
+    readback.finish()
+}
@@ -0,0 +1,549 @@
+use crate::term::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+
+/// A lambda term using de Bruijn indices instead of variable names.
+///
+/// A variable is the number of binders between its occurrence and the
+/// abstraction that binds it, so `λx. λy. x` is `λ λ 1`. Indices that point
+/// past all enclosing binders refer to free variables.
+///
+/// Abstractions keep the name of their parameter as a hint for converting back
+/// to a [`Term`], but the hint is ignored by `PartialEq` and `Hash`, so two
+/// `DbTerm`s are equal iff the terms they represent are α-equivalent.
+///
+/// Like [`Term`], all traversals use an explicit stack.
+#[derive(Eq)]
+pub enum DbTerm {
+    Var(usize),
+    Abs(Symbol, Box<DbTerm>),
+    App(Box<DbTerm>, Box<DbTerm>),
+}
+
+impl DbTerm {
+    /// Converts a named term. Also returns the names of the free variables
+    /// in order of their first occurrence: the `i`-th free variable is
+    /// represented by index `i` plus the number of enclosing binders.
+    ///
+    /// Example: `λx. x y` becomes `λ 0 1` with free variables `["y"]`.
+    pub fn from_term(term: &Term) -> (DbTerm, Vec<Symbol>) {
+        let mut conversion = FromTerm {
+            free: Vec::new(),
+            free_indices: HashMap::new(),
+            scopes: HashMap::new(),
+            depth: 0,
+        };
+        let result = fold(term, &mut conversion);
+        (result, conversion.free)
+    }
+
+    /// Converts back to a named term, with `free` naming the free variables
+    /// as returned by [`DbTerm::from_term`].
+    ///
+    /// Abstractions get their hint as parameter name, unless that would
+    /// capture a variable, in which case a fresh name like `x_1` is used.
+    /// So converting a term to de Bruijn indices and back yields exactly the
+    /// original term. Free indices without a name in `free` are named `_i`.
+    pub fn to_term(&self, free: &[Symbol]) -> Term {
+        let renamed = self.binders_to_rename(free);
+        let free_name = |i: usize| {
+            free.get(i)
+                .copied()
+                .unwrap_or_else(|| Symbol::intern(&format!("_{}", i)))
+        };
+
+        let mut used_names: HashSet<Symbol> = free.iter().copied().collect();
+        let mut stack = vec![self];
+        while let Some(term) = stack.pop() {
+            match term {
+                DbTerm::Var(_) => {}
+                DbTerm::Abs(hint, body) => {
+                    used_names.insert(*hint);
+                    stack.push(body);
+                }
+                DbTerm::App(t1, t2) => {
+                    stack.push(t2);
+                    stack.push(t1);
+                }
+            }
+        }
+
+        let mut conversion = ToTerm {
+            free_name,
+            renamed,
+            used_names,
+            binders: Vec::new(),
+            counters: HashMap::new(),
+            abs_count: 0,
+        };
+        fold(self, &mut conversion)
+    }
+
+    /// Decides which abstractions, numbered in pre-order, cannot keep their hint.
+    ///
+    /// A binder must be renamed if a variable in its body refers to an outer
+    /// binder or free variable of the same name. Renamed binders get fresh
+    /// names, so they never capture anything themselves.
+    fn binders_to_rename(&self, free: &[Symbol]) -> Vec<bool> {
+        enum Task<'a> {
+            Visit(&'a DbTerm),
+            Exit,
+        }
+
+        let mut renamed: Vec<bool> = Vec::new();
+        // enclosing binders as (pre-order number, hint), innermost last
+        let mut binders: Vec<(usize, Symbol)> = Vec::new();
+        // positions in `binders` of the binders that are still named by their hint
+        let mut named: HashMap<Symbol, Vec<usize>> = HashMap::new();
+
+        let mut tasks = vec![Task::Visit(self)];
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Visit(DbTerm::Var(i)) => match binders.len().checked_sub(i + 1) {
+                    Some(target) => {
+                        let (id, name) = binders[target];
+                        if renamed[id] {
+                            continue;
+                        }
+                        // every binder named like the target between it and the occurrence would capture it
+                        let positions = named.get_mut(&name).expect("binder is in scope");
+                        while let Some(&position) = positions.last().filter(|&&p| p != target) {
+                            renamed[binders[position].0] = true;
+                            positions.pop();
+                        }
+                    }
+                    None => {
+                        let index = i - binders.len();
+                        if let Some(positions) = free.get(index).and_then(|x| named.get_mut(x)) {
+                            for position in positions.drain(..) {
+                                renamed[binders[position].0] = true;
+                            }
+                        }
+                    }
+                },
+                Task::Visit(DbTerm::Abs(hint, body)) => {
+                    named.entry(*hint).or_default().push(binders.len());
+                    binders.push((renamed.len(), *hint));
+                    renamed.push(false);
+                    tasks.push(Task::Exit);
+                    tasks.push(Task::Visit(body));
+                }
+                Task::Visit(DbTerm::App(t1, t2)) => {
+                    tasks.push(Task::Visit(t2));
+                    tasks.push(Task::Visit(t1));
+                }
+                Task::Exit => {
+                    let (id, hint) = binders.pop().expect("unbalanced binders");
+                    if !renamed[id] {
+                        named.get_mut(&hint).and_then(|positions| positions.pop());
+                    }
+                }
+            }
+        }
+        renamed
+    }
+
+    /// Adds `amount` to all indices of at least `cutoff`, i.e. to all variables
+    /// that are free when `cutoff` binders are ignored.
+    pub fn shift(&self, amount: isize, cutoff: usize) -> DbTerm {
+        self.map_vars(|i, depth| {
+            if i >= cutoff + depth {
+                DbTerm::Var(i.checked_add_signed(amount).expect("shifted below zero"))
+            } else {
+                DbTerm::Var(i)
+            }
+        })
+    }
+
+    /// Replaces the variable with index `index` by `replacement`.
+    /// Under binders, both are shifted accordingly, so nothing is captured.
+    pub fn substitute(&self, index: usize, replacement: &DbTerm) -> DbTerm {
+        self.map_vars(|i, depth| {
+            if i == index + depth {
+                replacement.shift(depth as isize, 0)
+            } else {
+                DbTerm::Var(i)
+            }
+        })
+    }
+
+    /// Contracts the redex `(λ body) argument`, i.e. substitutes `argument` for
+    /// the variable bound by the abstraction and removes that binder.
+    pub fn beta(body: &DbTerm, argument: &DbTerm) -> DbTerm {
+        body.map_vars(|i, depth| match i.cmp(&depth) {
+            std::cmp::Ordering::Less => DbTerm::Var(i),
+            std::cmp::Ordering::Equal => argument.shift(depth as isize, 0),
+            std::cmp::Ordering::Greater => DbTerm::Var(i - 1),
+        })
+    }
+
+    /// Rebuilds the term, replacing every variable with index `i` under
+    /// `depth` binders by `f(i, depth)`.
+    fn map_vars(&self, f: impl FnMut(usize, usize) -> DbTerm) -> DbTerm {
+        fold(self, &mut MapVars { f, depth: 0 })
+    }
+}
+
+/// The state of [`DbTerm::from_term`].
+struct FromTerm {
+    free: Vec<Symbol>,
+    free_indices: HashMap<Symbol, usize>,
+    /// Depths of the enclosing binders of each name.
+    scopes: HashMap<Symbol, Vec<usize>>,
+    depth: usize,
+}
+
+impl<'a> Fold<&'a Term> for FromTerm {
+    type Binder = Symbol;
+    type Output = DbTerm;
+
+    fn visit(&mut self, term: &'a Term) -> Visit<&'a Term, Symbol, DbTerm> {
+        match term {
+            Term::Var(x) => {
+                let index = match self.scopes.get(x).and_then(|depths| depths.last()) {
+                    Some(binder) => self.depth - 1 - binder,
+                    None => {
+                        let free = &mut self.free;
+                        let i = *self.free_indices.entry(*x).or_insert_with(|| {
+                            free.push(*x);
+                            free.len() - 1
+                        });
+                        self.depth + i
+                    }
+                };
+                Visit::Done(DbTerm::Var(index))
+            }
+            Term::Abs(param, body) => {
+                self.scopes.entry(*param).or_default().push(self.depth);
+                self.depth += 1;
+                Visit::Abs(*param, body)
+            }
+            Term::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+
+    fn abs(&mut self, param: Symbol, body: DbTerm) -> DbTerm {
+        self.depth -= 1;
+        self.scopes.get_mut(&param).and_then(|depths| depths.pop());
+        DbTerm::Abs(param, Box::new(body))
+    }
+
+    fn app(&mut self, t1: DbTerm, t2: DbTerm) -> DbTerm {
+        DbTerm::App(Box::new(t1), Box::new(t2))
+    }
+}
+
+/// The state of [`DbTerm::to_term`].
+struct ToTerm<F> {
+    free_name: F,
+    /// Whether each abstraction, numbered in pre-order, needs a fresh name.
+    renamed: Vec<bool>,
+    used_names: HashSet<Symbol>,
+    /// Names of the enclosing binders, innermost last.
+    binders: Vec<Symbol>,
+    counters: HashMap<Symbol, usize>,
+    abs_count: usize,
+}
+
+impl<'a, F: Fn(usize) -> Symbol> Fold<&'a DbTerm> for ToTerm<F> {
+    type Binder = ();
+    type Output = Term;
+
+    fn visit(&mut self, term: &'a DbTerm) -> Visit<&'a DbTerm, (), Term> {
+        match term {
+            DbTerm::Var(i) => {
+                let name = match self.binders.len().checked_sub(i + 1) {
+                    Some(binder) => self.binders[binder],
+                    None => (self.free_name)(i - self.binders.len()),
+                };
+                Visit::Done(Term::Var(name))
+            }
+            DbTerm::Abs(hint, body) => {
+                let name = if self.renamed[self.abs_count] {
+                    let counter = self.counters.entry(*hint).or_insert(1);
+                    let mut name = hint.numbered(*counter);
+                    while self.used_names.contains(&name) {
+                        *counter += 1;
+                        name = hint.numbered(*counter);
+                    }
+                    self.used_names.insert(name);
+                    name
+                } else {
+                    *hint
+                };
+                self.abs_count += 1;
+                self.binders.push(name);
+                Visit::Abs((), body)
+            }
+            DbTerm::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+
+    fn abs(&mut self, _: (), body: Term) -> Term {
+        let name = self.binders.pop().expect("unbalanced binders");
+        Term::Abs(name, Box::new(body))
+    }
+
+    fn app(&mut self, t1: Term, t2: Term) -> Term {
+        Term::App(Box::new(t1), Box::new(t2))
+    }
+}
+
+/// The state of [`DbTerm::map_vars`].
+struct MapVars<F> {
+    f: F,
+    depth: usize,
+}
+
+impl<'a, F: FnMut(usize, usize) -> DbTerm> Fold<&'a DbTerm> for MapVars<F> {
+    type Binder = Symbol;
+    type Output = DbTerm;
+
+    fn visit(&mut self, term: &'a DbTerm) -> Visit<&'a DbTerm, Symbol, DbTerm> {
+        match term {
+            DbTerm::Var(i) => Visit::Done((self.f)(*i, self.depth)),
+            DbTerm::Abs(hint, body) => {
+                self.depth += 1;
+                Visit::Abs(*hint, body)
+            }
+            DbTerm::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+
+    fn abs(&mut self, hint: Symbol, body: DbTerm) -> DbTerm {
+        self.depth -= 1;
+        DbTerm::Abs(hint, Box::new(body))
+    }
+
+    fn app(&mut self, t1: DbTerm, t2: DbTerm) -> DbTerm {
+        DbTerm::App(Box::new(t1), Box::new(t2))
+    }
+}
+
+impl Clone for DbTerm {
+    fn clone(&self) -> Self {
+        self.map_vars(|i, _| DbTerm::Var(i))
+    }
+}
+
+/// Compares the structure and indices, ignoring the name hints.
+impl PartialEq for DbTerm {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (DbTerm::Var(i), DbTerm::Var(j)) if i == j => {}
+                (DbTerm::Abs(_, body1), DbTerm::Abs(_, body2)) => stack.push((body1, body2)),
+                (DbTerm::App(t1, t2), DbTerm::App(u1, u2)) => {
+                    stack.push((t2, u2));
+                    stack.push((t1, u1));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Hashes the structure and indices, ignoring the name hints.
+impl Hash for DbTerm {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+        while let Some(term) = stack.pop() {
+            match term {
+                DbTerm::Var(i) => {
+                    state.write_u8(0);
+                    state.write_usize(*i);
+                }
+                DbTerm::Abs(_, body) => {
+                    state.write_u8(1);
+                    stack.push(body);
+                }
+                DbTerm::App(t1, t2) => {
+                    state.write_u8(2);
+                    stack.push(t2);
+                    stack.push(t1);
+                }
+            }
+        }
+    }
+}
+
+/// Prints the term in de Bruijn notation without the hints, e.g. `λ λ 1 0`,
+/// with the same parentheses as the pretty printer. Not derived, since a
+/// derived implementation would recurse.
+impl fmt::Debug for DbTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Item<'a> {
+            Term(&'a DbTerm, bool),
+            Text(&'static str),
+        }
+
+        // see `pretty_print_with` for the meaning of the flag
+        let mut stack = vec![Item::Term(self, false)];
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Text(text) => f.write_str(text)?,
+                Item::Term(DbTerm::Var(index), _) => write!(f, "{}", index)?,
+                Item::Term(DbTerm::Abs(_, body), guard_lambda) => {
+                    if guard_lambda {
+                        f.write_str("(")?;
+                        stack.push(Item::Text(")"));
+                    }
+                    f.write_str("λ ")?;
+                    stack.push(Item::Term(body, false));
+                }
+                Item::Term(DbTerm::App(t1, t2), guard_lambda) => {
+                    match **t2 {
+                        DbTerm::App(_, _) => {
+                            stack.push(Item::Text(")"));
+                            stack.push(Item::Term(t2, false));
+                            stack.push(Item::Text("("));
+                        }
+                        _ => stack.push(Item::Term(t2, guard_lambda)),
+                    }
+                    stack.push(Item::Text(" "));
+                    match **t1 {
+                        DbTerm::Abs(_, _) => {
+                            stack.push(Item::Text(")"));
+                            stack.push(Item::Term(t1, false));
+                            stack.push(Item::Text("("));
+                        }
+                        _ => stack.push(Item::Term(t1, true)),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DbTerm {
+    fn drop(&mut self) {
+        // see the `Drop` implementation of `Term`
+        let mut stack = Vec::new();
+        take_children(self, &mut stack);
+        while let Some(mut term) = stack.pop() {
+            take_children(&mut term, &mut stack);
+        }
+    }
+}
+
+fn take_children(term: &mut DbTerm, stack: &mut Vec<DbTerm>) {
+    match term {
+        DbTerm::Var(_) => {}
+        DbTerm::Abs(_, body) => stack.push(mem::replace(&mut **body, DbTerm::Var(0))),
+        DbTerm::App(t1, t2) => {
+            stack.push(mem::replace(&mut **t1, DbTerm::Var(0)));
+            stack.push(mem::replace(&mut **t2, DbTerm::Var(0)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn db_abs(hint: &str, body: DbTerm) -> DbTerm {
+        DbTerm::Abs(Symbol::intern(hint), Box::new(body))
+    }
+
+    fn db_app(t1: DbTerm, t2: DbTerm) -> DbTerm {
+        DbTerm::App(Box::new(t1), Box::new(t2))
+    }
+
+    #[test]
+    fn test_from_term() {
+        // λx. λy. x y z  is  λ λ 1 0 2
+        let term = abs("x", abs("y", app(app(var("x"), var("y")), var("z"))));
+        let (db, free) = DbTerm::from_term(&term);
+        let expected = db_abs(
+            "x",
+            db_abs("y", db_app(db_app(DbTerm::Var(1), DbTerm::Var(0)), DbTerm::Var(2))),
+        );
+        assert_eq!(db, expected);
+        assert_eq!(free, vec![Symbol::intern("z")]);
+    }
+
+    #[test]
+    fn test_debug() {
+        let (db, _) = DbTerm::from_term(&parse("(λx. λy. x y z) (λx. x) (u v)").unwrap());
+        assert_eq!(format!("{db:?}"), "(λ λ 1 0 2) (λ 0) (1 2)");
+    }
+
+    #[test]
+    fn test_hints_are_ignored_by_equality() {
+        let (db1, _) = DbTerm::from_term(&abs("x", var("x")));
+        let (db2, _) = DbTerm::from_term(&abs("y", var("y")));
+        assert_eq!(db1, db2);
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let terms = vec![
+            var("x"),
+            abs("x", abs("x", var("x"))),
+            abs("x", app(app(var("x"), abs("x", var("x"))), var("x"))),
+            abs("x", abs("y", app(var("y"), abs("x", app(var("x"), var("y")))))),
+            app(abs("y", app(var("x"), var("y"))), abs("x", var("y"))),
+        ];
+        for term in terms {
+            let (db, free) = DbTerm::from_term(&term);
+            assert_eq!(db.to_term(&free), term);
+        }
+    }
+
+    #[test]
+    fn test_to_term_avoids_capture() {
+        // λ 0 1 with free variable y must not become λy. y y
+        let db = db_abs("y", db_app(DbTerm::Var(0), DbTerm::Var(1)));
+        let term = db.to_term(&[Symbol::intern("y")]);
+        assert_eq!(term, abs("y_1", app(var("y_1"), var("y"))));
+
+        // λ λ 1 with both hints x only renames the inner binder
+        let db = db_abs("x", db_abs("x", DbTerm::Var(1)));
+        assert_eq!(db.to_term(&[]), abs("x", abs("x_1", var("x"))));
+    }
+
+    #[test]
+    fn test_shift_and_substitute() {
+        // λ 0 1, shifted by 2, is λ 0 3
+        let db = db_abs("x", db_app(DbTerm::Var(0), DbTerm::Var(1)));
+        assert_eq!(db.shift(2, 0), db_abs("x", db_app(DbTerm::Var(0), DbTerm::Var(3))));
+
+        // (λ 0 1)[0 := 5] is λ 0 6
+        let replaced = db.substitute(0, &DbTerm::Var(5));
+        assert_eq!(replaced, db_abs("x", db_app(DbTerm::Var(0), DbTerm::Var(6))));
+    }
+
+    #[test]
+    fn test_beta() {
+        // (λx. λy. x) y  reduces to  λy_1. y
+        let term = app(abs("x", abs("y", var("x"))), var("y"));
+        let (db, free) = DbTerm::from_term(&term);
+        let DbTerm::App(function, argument) = &db else {
+            panic!("expected an application");
+        };
+        let DbTerm::Abs(_, body) = &**function else {
+            panic!("expected an abstraction");
+        };
+        let reduced = DbTerm::beta(body, argument);
+        assert_eq!(reduced, db_abs("y", DbTerm::Var(1)));
+        assert_eq!(reduced.to_term(&free), abs("y_1", var("y")));
+    }
+
+    #[test]
+    fn test_deep_term_is_stack_safe() {
+        let mut term = var("y");
+        for _ in 0..200_000 {
+            term = abs("x", app(var("x"), term));
+        }
+        let (db, free) = DbTerm::from_term(&term);
+        assert_eq!(db.clone(), db);
+        assert!(format!("{db:?}").starts_with("λ 0 λ 0 λ"));
+        assert_eq!(db.to_term(&free), term);
+    }
+}
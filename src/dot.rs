@@ -0,0 +1,212 @@
+use crate::eval::*;
+use crate::graph::*;
+use crate::term::*;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Renders the syntax tree of a term in the DOT language of Graphviz.
+///
+/// Abstractions are labelled `λx`, applications `@` and variables by their
+/// name. Every bound variable has a dashed edge back to its binder, so that
+/// shadowing is visible at a glance.
+///
+/// Example: `λx. x` renders as
+/// ```text
+/// digraph term {
+///   n0 [label="λx"];
+///   n1 [label="x"];
+///   n0 -> n1;
+///   n1 -> n0 [style=dashed, constraint=false];
+/// }
+/// ```
+pub fn to_dot(term: &Term) -> String {
+    enum Task<'a> {
+        Visit(&'a Term, Option<usize>),
+        Unbind(Symbol),
+    }
+
+    let mut nodes = String::new();
+    let mut edges = String::new();
+    let mut count = 0;
+    // node numbers of the enclosing binders of each name
+    let mut binders: HashMap<Symbol, Vec<usize>> = HashMap::new();
+    let mut tasks = vec![Task::Visit(term, None)];
+    while let Some(task) = tasks.pop() {
+        let (term, parent) = match task {
+            Task::Visit(term, parent) => (term, parent),
+            Task::Unbind(param) => {
+                binders.get_mut(&param).and_then(|nodes| nodes.pop());
+                continue;
+            }
+        };
+        let node = count;
+        count += 1;
+        if let Some(parent) = parent {
+            writeln!(edges, "  n{} -> n{};", parent, node).unwrap();
+        }
+        match term {
+            Term::Var(x) => {
+                writeln!(nodes, "  n{} [label=\"{}\"];", node, escape(x.as_str())).unwrap();
+                if let Some(binder) = binders.get(x).and_then(|nodes| nodes.last()) {
+                    writeln!(edges, "  n{} -> n{} [style=dashed, constraint=false];", node, binder).unwrap();
+                }
+            }
+            Term::Abs(param, body) => {
+                writeln!(nodes, "  n{} [label=\"λ{}\"];", node, escape(param.as_str())).unwrap();
+                binders.entry(*param).or_default().push(node);
+                tasks.push(Task::Unbind(*param));
+                tasks.push(Task::Visit(body, Some(node)));
+            }
+            Term::App(t1, t2) => {
+                writeln!(nodes, "  n{} [label=\"@\"];", node).unwrap();
+                tasks.push(Task::Visit(t2, Some(node)));
+                tasks.push(Task::Visit(t1, Some(node)));
+            }
+        }
+    }
+    format!("digraph term {{\n{}{}}}\n", nodes, edges)
+}
+
+/// Renders the reduction sequence of `term` under `strategy` in the DOT
+/// language, with at most `max_steps` steps. Each edge is labelled with the
+/// position of the contracted redex; a normal form has a double border.
+pub fn trace_to_dot(term: &Term, strategy: Strategy, max_steps: usize) -> String {
+    let mut terms = vec![term.clone()];
+    let mut edges = Vec::new();
+    for (i, step) in trace(term, strategy).take(max_steps).enumerate() {
+        edges.push(Edge {
+            from: i,
+            to: i + 1,
+            redex: step.redex,
+        });
+        terms.push(step.term);
+    }
+    let last = terms.len() - 1;
+    let finished = find_redex(&terms[last], strategy).is_none();
+    reductions_to_dot("trace", &terms, &edges, None, |i| {
+        if i == last && finished { ", peripheries=2" } else { "" }
+    })
+}
+
+/// Renders a reduction graph in the DOT language. Edges are labelled with the
+/// positions of the contracted redexes and normal forms have a double border.
+/// If the graph was cut off by its limits, it says so in its label, and terms
+/// with reductions that were not followed have a dashed border.
+pub fn graph_to_dot(graph: &ReductionGraph) -> String {
+    let normal_forms = graph.normal_forms();
+    let mut reductions = vec![0; graph.terms.len()];
+    for edge in &graph.edges {
+        reductions[edge.from] += 1;
+    }
+    let label = graph.truncated.then_some("incomplete, the limits were reached");
+    reductions_to_dot("reductions", &graph.terms, &graph.edges, label, |i| {
+        if normal_forms.contains(&i) {
+            ", peripheries=2"
+        } else if redexes(&graph.terms[i]).len() > reductions[i] {
+            ", style=dashed"
+        } else {
+            ""
+        }
+    })
+}
+
+/// Renders terms as boxes with the given extra attributes, connected by edges.
+fn reductions_to_dot(
+    name: &str,
+    terms: &[Term],
+    edges: &[Edge],
+    label: Option<&str>,
+    attributes: impl Fn(usize) -> &'static str,
+) -> String {
+    let mut out = format!("digraph {} {{\n", name);
+    if let Some(label) = label {
+        writeln!(out, "  label=\"{}\";", escape(label)).unwrap();
+    }
+    out.push_str("  node [shape=box];\n");
+    for (i, term) in terms.iter().enumerate() {
+        writeln!(out, "  t{} [label=\"{}\"{}];", i, escape(&term.to_string()), attributes(i)).unwrap();
+    }
+    for edge in edges {
+        let label = escape(&format_path(&edge.redex));
+        writeln!(out, "  t{} -> t{} [label=\"{}\"];", edge.from, edge.to, label).unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Escapes a label for use in a double-quoted DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_to_dot() {
+        let dot = to_dot(&abs("x", var("x")));
+        assert_eq!(
+            dot,
+            "digraph term {\n  n0 [label=\"λx\"];\n  n1 [label=\"x\"];\n  n0 -> n1;\n  \
+             n1 -> n0 [style=dashed, constraint=false];\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_to_dot_binder_edges() {
+        // λx. λx. x y: the variable x refers to the inner binder, y is free
+        let dot = to_dot(&parse("λx. λx. x y").unwrap());
+        assert!(dot.contains("n3 -> n1 [style=dashed, constraint=false];"));
+        assert!(!dot.contains("n3 -> n0 [style"));
+        assert!(!dot.contains("n4 -> "));
+    }
+
+    #[test]
+    fn test_trace_to_dot() {
+        let term = parse("(λx. x) ((λy. y) z)").unwrap();
+        let dot = trace_to_dot(&term, Strategy::NormalOrder, 10);
+        assert!(dot.starts_with("digraph trace {"));
+        assert!(dot.contains("t0 -> t1 [label=\"root\"];"));
+        assert!(dot.contains("t2 [label=\"z\", peripheries=2];"));
+
+        // a cut off trace does not mark its last term
+        let dot = trace_to_dot(&term, Strategy::NormalOrder, 1);
+        assert!(!dot.contains("peripheries"));
+    }
+
+    #[test]
+    fn test_graph_to_dot() {
+        let graph = reduction_graph(&parse("(λx. x) y").unwrap(), GraphLimits::default());
+        assert_eq!(
+            graph_to_dot(&graph),
+            "digraph reductions {\n  node [shape=box];\n  t0 [label=\"(λx. x) y\"];\n  \
+             t1 [label=\"y\", peripheries=2];\n  t0 -> t1 [label=\"root\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_graph_to_dot_truncated() {
+        // (λx. x x x) (λx. x x x) grows forever
+        let limits = GraphLimits {
+            max_depth: 1,
+            ..GraphLimits::default()
+        };
+        let graph = reduction_graph(&parse("(λx. x x x) (λx. x x x)").unwrap(), limits);
+        let dot = graph_to_dot(&graph);
+        assert!(dot.starts_with("digraph reductions {\n  label=\"incomplete, the limits were reached\";\n"));
+        assert!(dot.contains("t0 [label=\"(λx. x x x) λx. x x x\"];"));
+        assert!(dot.contains("t1 [label=\"(λx. x x x) (λx. x x x) λx. x x x\", style=dashed];"));
+
+        // a complete graph has neither
+        let graph = reduction_graph(&parse("(λx. x) y").unwrap(), GraphLimits::default());
+        assert!(!graph_to_dot(&graph).contains("incomplete"));
+        assert!(!graph_to_dot(&graph).contains("dashed"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a \"b\" \\c"), "a \\\"b\\\" \\\\c");
+    }
+}
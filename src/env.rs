@@ -0,0 +1,98 @@
+use crate::eval::*;
+use crate::term::*;
+
+/// Top-level definitions made with `def name = term`.
+///
+/// Definitions are lexically scoped: a definition refers to the definitions
+/// made before it, and redefining a name does not change earlier definitions
+/// that used it.
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    /// Definitions in the order they were made, each already resolved.
+    defs: Vec<(Symbol, Term)>,
+}
+
+impl Env {
+    /// Creates an environment without definitions.
+    pub fn new() -> Self {
+        Env::default()
+    }
+
+    /// Binds `name` to `term`, replacing any previous definition of `name`.
+    /// Names defined earlier are resolved in `term` right away.
+    pub fn define(&mut self, name: impl Into<Symbol>, term: &Term) {
+        let name = name.into();
+        let term = self.resolve(term);
+        self.defs.retain(|(defined, _)| *defined != name);
+        self.defs.push((name, term));
+    }
+
+    /// The term bound to `name`, if any.
+    pub fn get(&self, name: impl Into<Symbol>) -> Option<&Term> {
+        let name = name.into();
+        self.defs
+            .iter()
+            .find(|(defined, _)| *defined == name)
+            .map(|(_, term)| term)
+    }
+
+    /// All definitions in the order they were made.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &Term)> {
+        self.defs.iter().map(|(name, term)| (*name, term))
+    }
+
+    /// Replaces the free variables of `term` that are defined by their definitions.
+    ///
+    /// Each definition only mentions names that were undefined when it was made,
+    /// so substituting the latest definitions first never resolves a name
+    /// inside a definition that was made before that name was defined.
+    pub fn resolve(&self, term: &Term) -> Term {
+        let mut resolved = term.clone();
+        for (name, definition) in self.defs.iter().rev() {
+            if free_variables(&resolved).contains(name) {
+                resolved = substitute(&resolved, *name, definition);
+            }
+        }
+        resolved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_definitions() {
+        let mut env = Env::new();
+        env.define("id", &abs("x", var("x")));
+        env.define("k", &abs("x", abs("y", var("x"))));
+        let term = app(app(var("k"), var("id")), var("z"));
+        assert_eq!(eval(&env.resolve(&term)), abs("x", var("x")));
+    }
+
+    #[test]
+    fn test_definitions_refer_to_earlier_definitions() {
+        let mut env = Env::new();
+        env.define("id", &abs("x", var("x")));
+        env.define("twice", &abs("f", app(var("id"), var("f"))));
+        assert_eq!(
+            env.get("twice"),
+            Some(&abs("f", app(abs("x", var("x")), var("f"))))
+        );
+    }
+
+    #[test]
+    fn test_definitions_are_lexically_scoped() {
+        let mut env = Env::new();
+        env.define("a", &var("b"));
+        env.define("b", &var("c"));
+        // `a` was defined before `b`, so the `b` in it stays free
+        assert_eq!(env.resolve(&var("a")), var("b"));
+        assert_eq!(env.resolve(&app(var("a"), var("b"))), app(var("b"), var("c")));
+
+        env.define("b", &var("d"));
+        let names: Vec<_> = env.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(env.get("b"), Some(&var("d")));
+    }
+}
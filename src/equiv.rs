@@ -0,0 +1,112 @@
+use crate::alpha::*;
+use crate::eval::*;
+use crate::term::*;
+use std::fmt;
+
+/// The outcome of comparing two terms with [`beta_eq`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Equivalence {
+    /// The terms have the same normal form up to α-equivalence.
+    Equal,
+    /// The terms have different normal forms, so by the Church–Rosser
+    /// theorem they are not convertible.
+    NotEqual,
+    /// At least one term did not reach its normal form within the budget.
+    /// It may have none, in which case equality is undecidable in general.
+    Unknown,
+}
+
+impl fmt::Display for Equivalence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Equivalence::Equal => write!(f, "Equal"),
+            Equivalence::NotEqual => write!(f, "Not equal"),
+            Equivalence::Unknown => write!(f, "Unknown, no normal form found within the step limit"),
+        }
+    }
+}
+
+/// Checks whether two terms are β-equivalent, i.e. equal as programs.
+///
+/// Both terms are normalized with normal order reduction, using at most
+/// `fuel` steps each, and the normal forms are compared up to α-equivalence.
+///
+/// Examples:
+///   `(λx. x) y` and `y` are equal.
+///   `λx. x` and `λx. λy. x` are not equal.
+///   `(λx. x x) (λx. x x)` and `y` are unknown, as the former diverges.
+pub fn beta_eq(t1: &Term, t2: &Term, fuel: usize) -> Equivalence {
+    equivalence(t1, t2, fuel, false)
+}
+
+/// Checks whether two terms are βη-equivalent, like [`beta_eq`] but comparing
+/// βη-normal forms, so that e.g. `λx. f x` and `f` are equal.
+pub fn beta_eta_eq(t1: &Term, t2: &Term, fuel: usize) -> Equivalence {
+    equivalence(t1, t2, fuel, true)
+}
+
+fn equivalence(t1: &Term, t2: &Term, fuel: usize, eta: bool) -> Equivalence {
+    // α-equivalent terms are equal even without a normal form
+    if alpha_eq(t1, t2) {
+        return Equivalence::Equal;
+    }
+    let limits = Limits::steps(fuel);
+    let (Ok(nf1), Ok(nf2)) = (
+        eval_limited(t1, Strategy::NormalOrder, limits),
+        eval_limited(t2, Strategy::NormalOrder, limits),
+    ) else {
+        return Equivalence::Unknown;
+    };
+    let equal = if eta {
+        alpha_eq(&eta_reduce(&nf1), &eta_reduce(&nf2))
+    } else {
+        alpha_eq(&nf1, &nf2)
+    };
+    if equal {
+        Equivalence::Equal
+    } else {
+        Equivalence::NotEqual
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn check(t1: &str, t2: &str) -> Equivalence {
+        beta_eq(&parse(t1).unwrap(), &parse(t2).unwrap(), 1000)
+    }
+
+    #[test]
+    fn test_beta_eq() {
+        assert_eq!(check("(λx. x) y", "y"), Equivalence::Equal);
+        assert_eq!(check("λx. x", "(λf. f) (λy. y)"), Equivalence::Equal);
+        assert_eq!(check("λx. x", "λx. λy. x"), Equivalence::NotEqual);
+        assert_eq!(check("x", "y"), Equivalence::NotEqual);
+    }
+
+    #[test]
+    fn test_beta_eq_church_arithmetic() {
+        let plus = "(λm n f x. m f (n f x))";
+        let one = "(λf x. f x)";
+        let two = "(λg y. g (g y))";
+        assert_eq!(check(&format!("{plus} {one} {one}"), two), Equivalence::Equal);
+        assert_eq!(check(&format!("{plus} {one} {two}"), two), Equivalence::NotEqual);
+    }
+
+    #[test]
+    fn test_beta_eq_without_normal_form() {
+        let omega = "(λx. x x) (λx. x x)";
+        assert_eq!(check(omega, "y"), Equivalence::Unknown);
+        assert_eq!(check(omega, "(λy. y y) (λz. z z)"), Equivalence::Equal);
+    }
+
+    #[test]
+    fn test_beta_eta_eq() {
+        let t1 = parse("λx. f x").unwrap();
+        let t2 = parse("f").unwrap();
+        assert_eq!(beta_eq(&t1, &t2, 100), Equivalence::NotEqual);
+        assert_eq!(beta_eta_eq(&t1, &t2, 100), Equivalence::Equal);
+    }
+}
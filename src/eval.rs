@@ -1,132 +1,834 @@
 use crate::term::*;
-use std::collections::HashSet;
-
-/// Evaluates a term:
-/// In order to simplify the term as much as possible, we use a call-by-value strategy.
-/// We even evaluate the body of an abstraction eagerly.
-/// 
-/// The evaluation rules are as follows:
-/// - A variable evaluates to itself.
-/// - An abstraction evaluates to itself, even the body is eagerly evaluated.
-/// - An application evaluates the left side, then the right side, 
-///   and applies the left side to the right side by substitution.
-///   Examples: 
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// A reduction strategy, i.e. the choice of which redex to contract next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Contract the leftmost-outermost redex first, even under abstractions.
+    /// Reaches the β-normal form whenever one exists.
+    #[default]
+    NormalOrder,
+    /// Contract the leftmost-innermost redex first, even under abstractions:
+    /// arguments are normalized before they are substituted.
+    ApplicativeOrder,
+    /// Contract the outermost redex in head position only, never reducing
+    /// arguments or under abstractions. Stops at weak head normal form.
+    CallByName,
+    /// Evaluate the function and then the argument before substituting,
+    /// never reducing under abstractions. Stops at weak head normal form.
+    CallByValue,
+    /// Contract the head redex only, also under abstractions.
+    /// Stops at head normal form `λx1. … λxn. y M1 … Mk`.
+    HeadReduction,
+}
+
+impl Strategy {
+    /// All strategies, in declaration order.
+    pub const ALL: [Strategy; 5] = [
+        Strategy::NormalOrder,
+        Strategy::ApplicativeOrder,
+        Strategy::CallByName,
+        Strategy::CallByValue,
+        Strategy::HeadReduction,
+    ];
+
+    /// Short name of the strategy, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::NormalOrder => "normal",
+            Strategy::ApplicativeOrder => "applicative",
+            Strategy::CallByName => "cbn",
+            Strategy::CallByValue => "cbv",
+            Strategy::HeadReduction => "head",
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    /// Parses the short name of a strategy, or one of the longer aliases
+    /// such as `call-by-value`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" | "normal-order" => Ok(Strategy::NormalOrder),
+            "applicative" | "applicative-order" => Ok(Strategy::ApplicativeOrder),
+            "cbn" | "call-by-name" => Ok(Strategy::CallByName),
+            "cbv" | "call-by-value" => Ok(Strategy::CallByValue),
+            "head" | "head-reduction" => Ok(Strategy::HeadReduction),
+            _ => Err(format!(
+                "Unknown strategy: {} (expected one of normal, applicative, cbn, cbv, head)",
+                s
+            )),
+        }
+    }
+}
+
+/// Evaluates a term to its β-normal form using normal order reduction.
+///
+/// Normal order always finds the normal form if the term has one, but
+/// does not terminate on terms without a normal form, e.g. `(λx. x x) (λx. x x)`.
+///
+/// Examples:
 ///   `x` evaluates to `x`.
 ///   `λx. x` evaluates to `λx. x`.
 ///   `(λx. x) y` evaluates to `y`.
 ///   `(λx. (λy. x)) z` evaluates to `λy. z`.
 ///   `(λx. (λy. x)) a b` evaluates to `a`.
 pub fn eval(term: &Term) -> Term {
-    // TODO: "Implement the eval function")
-    let term = term.clone();
-    match term.clone() {
-        Term::Var(_) => term,
-        Term::Abs(_, _) => term,
-        Term::App(t1, t2,) => match *t1 {
-            // if left side is abstraction, substitute right side!
-            Term::Abs(param, body) => substitute(
-                &eval(&body),
-                &param,
-                &eval(&t2),
+    eval_with(term, Strategy::NormalOrder)
+}
+
+/// Evaluates a term using the given reduction `strategy`.
+///
+/// The evaluator contracts one redex at a time and keeps no state on the
+/// native stack, so arbitrarily deep terms can be evaluated. The search for
+/// the next redex resumes where the last one was contracted, instead of
+/// starting over from the root.
+pub fn eval_with(term: &Term, strategy: Strategy) -> Term {
+    let mut current = term.clone();
+    let mut from = Vec::new();
+    while let Some(path) = find_redex_from(&current, strategy, &from) {
+        contract_in_place(&mut current, &path);
+        from = resume_point(path);
+    }
+    current
+}
+
+/// Evaluates a term to its βη-normal form: the β-normal form found by
+/// normal order reduction, with all η-redexes reduced by [`eta_reduce`].
+///
+/// Example: `λx. (λy. f y) x` evaluates to `f`.
+pub fn eval_beta_eta(term: &Term) -> Term {
+    // η-reduction never creates a β-redex in a β-normal form
+    eta_reduce(&eval(term))
+}
+
+/// Resource limits for [`eval_limited`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of β-reduction steps.
+    pub max_steps: usize,
+    /// Maximum size (number of nodes) of an intermediate term, if any.
+    pub max_size: Option<usize>,
+}
+
+impl Limits {
+    /// Limits the number of steps only.
+    pub fn steps(max_steps: usize) -> Self {
+        Limits {
+            max_steps,
+            max_size: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::steps(100_000)
+    }
+}
+
+/// Errors that abort a bounded evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    /// The step budget was used up before reaching normal form.
+    OutOfFuel { partial: Term, steps: usize },
+    /// An intermediate term grew beyond the size budget.
+    TermTooLarge {
+        partial: Term,
+        size: usize,
+        steps: usize,
+    },
+    /// The back end cannot evaluate with the strategy.
+    Unsupported { backend: Backend, strategy: Strategy },
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EvalError::OutOfFuel { partial, steps } => {
+                write!(f, "Out of fuel after {} steps, partial result: {}", steps, partial)
+            }
+            EvalError::TermTooLarge {
+                partial,
+                size,
+                steps,
+            } => write!(
+                f,
+                "Term grew to {} nodes after {} steps, partial result: {}",
+                size, steps, partial
             ),
+            EvalError::Unsupported { backend, strategy } => {
+                write!(f, "The {} back end does not support the {} strategy", backend, strategy)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
 
-            // otherwise, eval until it becomes one!
-            _ => Term::App(Box::new(eval(&t1)), t2),
+/// Evaluates a term using `strategy`, giving up once the `limits` are exceeded.
+///
+/// Unlike [`eval_with`], this terminates on every input, e.g.
+/// `(λx. x x) (λx. x x)` yields [`EvalError::OutOfFuel`] with the last
+/// intermediate term as `partial` result.
+pub fn eval_limited(term: &Term, strategy: Strategy, limits: Limits) -> Result<Term, EvalError> {
+    let mut current = term.clone();
+    let mut steps = 0;
+    let mut from = Vec::new();
+    loop {
+        if let Some(max_size) = limits.max_size {
+            let size = current.size();
+            if size > max_size {
+                return Err(EvalError::TermTooLarge {
+                    partial: current,
+                    size,
+                    steps,
+                });
+            }
         }
+        let Some(path) = find_redex_from(&current, strategy, &from) else {
+            return Ok(current);
+        };
+        if steps == limits.max_steps {
+            return Err(EvalError::OutOfFuel {
+                partial: current,
+                steps,
+            });
+        }
+        contract_in_place(&mut current, &path);
+        from = resume_point(path);
+        steps += 1;
     }
 }
 
-/// Replace all occurrences of a variable `var` in a `term` with `replacement`.
-pub fn substitute(term: &Term, var: &str, replacement: &Term) -> Term {
-    match term {
-        Term::Var(x) if x == var => replacement.clone(),
-        Term::Var(x) => Term::Var(x.clone()),
-        Term::Abs(param, body) if param != var => {
-            if free_variables(replacement).contains(param) {
-                // Prevent variable capture by renaming the parameter
-                let fresh_var = fresh_name(param, term, replacement);
-                // Rename the parameter in the body
-                let new_body = substitute(body, param, &Term::Var(fresh_var.clone()));
-                abs(&fresh_var, substitute(&new_body, var, replacement))
-            } else {
-                abs(param, substitute(body, var, replacement))
+/// An implementation of evaluation, see [`eval_on`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Contract one redex at a time by substitution, like [`eval_limited`].
+    /// Supports every strategy.
+    #[default]
+    Substitution,
+    /// The Krivine machine of [`crate::krivine`], which supports normal
+    /// order, call by name and head reduction.
+    Krivine,
+    /// The CEK machine of [`crate::cek`], which supports call by value.
+    Cek,
+    /// The SECD machine of [`crate::secd`], running compiled code with
+    /// call by value.
+    Secd,
+}
+
+impl Backend {
+    /// All back ends, in declaration order.
+    pub const ALL: [Backend; 4] = [Backend::Substitution, Backend::Krivine, Backend::Cek, Backend::Secd];
+
+    /// Short name of the back end, as accepted by `from_str`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Substitution => "substitution",
+            Backend::Krivine => "krivine",
+            Backend::Cek => "cek",
+            Backend::Secd => "secd",
+        }
+    }
+
+    /// Checks whether the back end can evaluate with `strategy`.
+    pub fn supports(self, strategy: Strategy) -> bool {
+        match self {
+            Backend::Substitution => true,
+            Backend::Krivine => matches!(
+                strategy,
+                Strategy::NormalOrder | Strategy::CallByName | Strategy::HeadReduction
+            ),
+            Backend::Cek | Backend::Secd => strategy == Strategy::CallByValue,
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "substitution" | "subst" => Ok(Backend::Substitution),
+            "krivine" => Ok(Backend::Krivine),
+            "cek" => Ok(Backend::Cek),
+            "secd" => Ok(Backend::Secd),
+            _ => Err(format!(
+                "Unknown back end: {} (expected one of substitution, krivine, cek, secd)",
+                s
+            )),
+        }
+    }
+}
+
+/// Evaluates a term using `strategy` on the given `backend`, giving up once
+/// the `limits` are exceeded.
+///
+/// All back ends agree with [`eval_limited`] up to α-equivalence and count
+/// the same β-reduction steps. When an abstract machine runs out of fuel,
+/// its state is read back into a term as partial result. The machines have
+/// no intermediate terms, so they check the size limit on the term they read
+/// back, i.e. the result or the partial result.
+pub fn eval_on(term: &Term, strategy: Strategy, limits: Limits, backend: Backend) -> Result<Term, EvalError> {
+    match backend {
+        Backend::Substitution => eval_limited(term, strategy, limits),
+        Backend::Krivine => crate::krivine::eval_krivine(term, strategy, limits),
+        Backend::Cek => crate::cek::eval_cek(term, strategy, limits),
+        Backend::Secd => crate::secd::eval_secd(term, strategy, limits),
+    }
+}
+
+/// One step on the way from a term to one of its subterms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// The left side (function) of an application.
+    Left,
+    /// The right side (argument) of an application.
+    Right,
+    /// The body of an abstraction.
+    Body,
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::Left => write!(f, "left"),
+            Direction::Right => write!(f, "right"),
+            Direction::Body => write!(f, "body"),
+        }
+    }
+}
+
+/// Formats a position in a term like `body.left`, or `root` for the term itself.
+pub fn format_path(path: &[Direction]) -> String {
+    if path.is_empty() {
+        return "root".to_string();
+    }
+    let directions: Vec<String> = path.iter().map(|direction| direction.to_string()).collect();
+    directions.join(".")
+}
+
+/// A single reduction step recorded by [`trace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// Position of the contracted redex in the term before the step.
+    pub redex: Vec<Direction>,
+    /// The term after contracting the redex.
+    pub term: Term,
+}
+
+/// Performs exactly one β-reduction step according to `strategy`.
+/// Returns `None` if the term contains no redex the strategy would contract,
+/// i.e. if it is in normal form with respect to the strategy.
+///
+/// Example: with normal order, `(λx. x) ((λy. y) z)` steps to `(λy. y) z`.
+pub fn step(term: &Term, strategy: Strategy) -> Option<Term> {
+    let path = find_redex(term, strategy)?;
+    Some(contract_at(term, &path))
+}
+
+/// Returns an iterator over all reduction steps of `term` under `strategy`.
+/// The iterator ends when the term reaches normal form with respect to the
+/// strategy, so it is infinite for diverging terms.
+pub fn trace(term: &Term, strategy: Strategy) -> Trace {
+    Trace {
+        term: Some(term.clone()),
+        strategy,
+    }
+}
+
+/// Iterator over the reduction steps of a term, created by [`trace`].
+#[derive(Debug, Clone)]
+pub struct Trace {
+    term: Option<Term>,
+    strategy: Strategy,
+}
+
+impl Iterator for Trace {
+    type Item = TraceStep;
+
+    fn next(&mut self) -> Option<TraceStep> {
+        let term = self.term.take()?;
+        let redex = find_redex(&term, self.strategy)?;
+        let next = contract_at(&term, &redex);
+        self.term = Some(next.clone());
+        Some(TraceStep { redex, term: next })
+    }
+}
+
+/// Finds the position of the redex that `strategy` would contract next.
+pub fn find_redex(term: &Term, strategy: Strategy) -> Option<Vec<Direction>> {
+    find_redex_from(term, strategy, &[])
+}
+
+/// Finds the redex that `strategy` would contract next, searching from the
+/// position `from` on: the strategy must not come across a redex before
+/// reaching `from`, e.g. because it is the [`resume_point`] of the last step.
+fn find_redex_from(term: &Term, strategy: Strategy, from: &[Direction]) -> Option<Vec<Direction>> {
+    match strategy {
+        Strategy::NormalOrder => find_outermost_redex(term, from),
+        Strategy::ApplicativeOrder => find_innermost_redex(term, from, true),
+        Strategy::CallByName => find_head_redex(term, from, false),
+        Strategy::CallByValue => find_innermost_redex(term, from, false),
+        Strategy::HeadReduction => find_head_redex(term, from, true),
+    }
+}
+
+/// Where to resume the search for the next redex after contracting the one
+/// at `path`. Only the subterm at `path` changed, and it can only have turned
+/// its parent into a redex if it is the function of an application.
+fn resume_point(mut path: Vec<Direction>) -> Vec<Direction> {
+    if path.last() == Some(&Direction::Left) {
+        path.pop();
+    }
+    path
+}
+
+/// Returns the subterm of `term` at `path`, if the path exists.
+pub fn subterm_at<'a>(term: &'a Term, path: &[Direction]) -> Option<&'a Term> {
+    let mut current = term;
+    for direction in path {
+        current = match (current, direction) {
+            (Term::App(t1, _), Direction::Left) => t1,
+            (Term::App(_, t2), Direction::Right) => t2,
+            (Term::Abs(_, body), Direction::Body) => body,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Lists the positions of all β-redexes in `term`, in the order normal order
+/// reduction would encounter them: outer redexes before the redexes they
+/// contain, and left before right.
+///
+/// Example: `(λx. x) ((λy. y) z)` has redexes at `[]` and `[Right]`.
+pub fn redexes(term: &Term) -> Vec<Vec<Direction>> {
+    enum Task<'a> {
+        Visit(&'a Term),
+        Enter(Direction),
+        Leave,
+    }
+
+    let mut found = Vec::new();
+    let mut path = Vec::new();
+    let mut tasks = vec![Task::Visit(term)];
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(term) => {
+                if is_redex(term) {
+                    found.push(path.clone());
+                }
+                // children are pushed in reverse order
+                match term {
+                    Term::Var(_) => {}
+                    Term::Abs(_, body) => {
+                        tasks.push(Task::Leave);
+                        tasks.push(Task::Visit(body));
+                        tasks.push(Task::Enter(Direction::Body));
+                    }
+                    Term::App(t1, t2) => {
+                        tasks.push(Task::Leave);
+                        tasks.push(Task::Visit(t2));
+                        tasks.push(Task::Enter(Direction::Right));
+                        tasks.push(Task::Leave);
+                        tasks.push(Task::Visit(t1));
+                        tasks.push(Task::Enter(Direction::Left));
+                    }
+                }
+            }
+            Task::Enter(direction) => path.push(direction),
+            Task::Leave => {
+                path.pop();
             }
         }
-        Term::App(t1, t2) => Term::App(
-            Box::new(substitute(t1, var, replacement)),
-            Box::new(substitute(t2, var, replacement)),
-        ),
-        _ => term.clone(),
     }
+    found
 }
 
-/// Collects free variables in a term.
-pub fn free_variables(term: &Term) -> HashSet<String> {
-    match term {
-        Term::Var(x) => {
-            let mut set = HashSet::new();
-            set.insert(x.clone());
-            set
+/// Contracts the redex at `path`, e.g. one returned by [`redexes`].
+/// Returns `None` if there is no redex at `path`.
+pub fn reduce_at(term: &Term, path: &[Direction]) -> Option<Term> {
+    if !subterm_at(term, path).is_some_and(is_redex) {
+        return None;
+    }
+    Some(contract_at(term, path))
+}
+
+fn is_redex(term: &Term) -> bool {
+    matches!(term, Term::App(t1, _) if matches!(**t1, Term::Abs(_, _)))
+}
+
+/// Contracts the redex `(λx. M) N` at `path` to `M[x := N]`.
+/// The path must point to a redex.
+fn contract_at(term: &Term, path: &[Direction]) -> Term {
+    let mut result = term.clone();
+    contract_in_place(&mut result, path);
+    result
+}
+
+fn contract_in_place(term: &mut Term, path: &[Direction]) {
+    let mut current = term;
+    for direction in path {
+        current = match (current, direction) {
+            (Term::App(t1, _), Direction::Left) => t1,
+            (Term::App(_, t2), Direction::Right) => t2,
+            (Term::Abs(_, body), Direction::Body) => body,
+            _ => unreachable!("path does not exist in term"),
+        };
+    }
+    *current = match current {
+        Term::App(t1, t2) => match &**t1 {
+            Term::Abs(param, body) => substitute(body, *param, t2),
+            _ => unreachable!("path does not point to a redex"),
+        },
+        _ => unreachable!("path does not point to a redex"),
+    };
+}
+
+/// Leftmost-outermost redex, found by a pre-order traversal starting at `from`.
+fn find_outermost_redex(term: &Term, from: &[Direction]) -> Option<Vec<Direction>> {
+    // continue with the arguments of the applications `from` is a function of
+    let mut stack = Vec::new();
+    let mut current = term;
+    for (depth, direction) in from.iter().enumerate() {
+        current = match (current, direction) {
+            (Term::App(t1, t2), Direction::Left) => {
+                stack.push((&**t2, Some(Direction::Right), depth));
+                t1
+            }
+            (Term::App(_, t2), Direction::Right) => t2,
+            (Term::Abs(_, body), Direction::Body) => body,
+            _ => unreachable!("path does not exist in term"),
+        };
+    }
+    let mut path = from.to_vec();
+    stack.push((current, None, from.len()));
+    while let Some((current, direction, depth)) = stack.pop() {
+        path.truncate(depth);
+        path.extend(direction);
+        if is_redex(current) {
+            return Some(path);
         }
-        Term::Abs(param, body) => {
-            let mut set = free_variables(body);
-            set.remove(param);
-            set
+        let depth = path.len();
+        match current {
+            Term::Var(_) => {}
+            Term::Abs(_, body) => stack.push((body, Some(Direction::Body), depth)),
+            Term::App(t1, t2) => {
+                stack.push((t2, Some(Direction::Right), depth));
+                stack.push((t1, Some(Direction::Left), depth));
+            }
         }
-        Term::App(t1, t2) => {
-            let mut set = free_variables(t1);
-            set.extend(free_variables(t2));
-            set
+    }
+    None
+}
+
+/// Leftmost-innermost redex, found by a post-order traversal starting at `from`.
+/// Abstraction bodies are only searched if `under_binders` is set.
+fn find_innermost_redex(term: &Term, from: &[Direction], under_binders: bool) -> Option<Vec<Direction>> {
+    enum Visit<'a> {
+        Enter(&'a Term, Option<Direction>, usize),
+        Exit(&'a Term, usize),
+    }
+
+    // continue with the applications `from` is part of, and their arguments
+    let mut stack = Vec::new();
+    let mut current = term;
+    for (depth, direction) in from.iter().enumerate() {
+        if let Term::App(..) = current {
+            stack.push(Visit::Exit(current, depth));
         }
+        current = match (current, direction) {
+            (Term::App(t1, t2), Direction::Left) => {
+                stack.push(Visit::Enter(t2, Some(Direction::Right), depth));
+                t1
+            }
+            (Term::App(_, t2), Direction::Right) => t2,
+            (Term::Abs(_, body), Direction::Body) => body,
+            _ => unreachable!("path does not exist in term"),
+        };
     }
+    let mut path = from.to_vec();
+    stack.push(Visit::Enter(current, None, from.len()));
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Enter(current, direction, depth) => {
+                path.truncate(depth);
+                path.extend(direction);
+                let depth = path.len();
+                match current {
+                    Term::Var(_) => {}
+                    Term::Abs(_, body) => {
+                        if under_binders {
+                            stack.push(Visit::Enter(body, Some(Direction::Body), depth));
+                        }
+                    }
+                    Term::App(t1, t2) => {
+                        stack.push(Visit::Exit(current, depth));
+                        stack.push(Visit::Enter(t2, Some(Direction::Right), depth));
+                        stack.push(Visit::Enter(t1, Some(Direction::Left), depth));
+                    }
+                }
+            }
+            Visit::Exit(current, depth) => {
+                if is_redex(current) {
+                    path.truncate(depth);
+                    return Some(path);
+                }
+            }
+        }
+    }
+    None
 }
 
-/// Generates a fresh variable name based on `base_name` that doesn't exist in `existing_vars`.
-fn fresh_name(base_name: &str, term: &Term, replacement: &Term) -> String {
-    let all_vars: HashSet<String> = collect_all_vars(term)
-        .union(&collect_all_vars(replacement))
-        .cloned()
-        .collect();
+/// Redex in head position, i.e. at the end of the spine of left sides of
+/// applications, searched from `from` on, which has to lie on that spine.
+/// Abstraction bodies are only entered if `under_binders` is set.
+fn find_head_redex(term: &Term, from: &[Direction], under_binders: bool) -> Option<Vec<Direction>> {
+    let mut path = from.to_vec();
+    let mut current = subterm_at(term, from).expect("path does not exist in term");
+    loop {
+        match current {
+            Term::Var(_) => return None,
+            Term::Abs(_, body) if under_binders => {
+                path.push(Direction::Body);
+                current = body;
+            }
+            Term::Abs(_, _) => return None,
+            Term::App(_, _) if is_redex(current) => return Some(path),
+            Term::App(t1, _) => {
+                path.push(Direction::Left);
+                current = t1;
+            }
+        }
+    }
+}
 
-    let mut fresh_var = base_name.to_string();
+/// Replace all occurrences of a variable `var` in a `term` with `replacement`.
+///
+/// Bound variables of `term` that would capture a free variable of
+/// `replacement` are renamed to fresh names.
+pub fn substitute(term: &Term, var: impl Into<Symbol>, replacement: &Term) -> Term {
+    let mut substitution = Substitution {
+        term,
+        var: var.into(),
+        replacement,
+        replacement_vars: free_variables(replacement),
+        used_vars: None,
+        counters: HashMap::new(),
+        binders: HashMap::new(),
+    };
+    fold(term, &mut substitution)
+}
+
+/// The state of [`substitute`].
+struct Substitution<'a> {
+    term: &'a Term,
+    var: Symbol,
+    replacement: &'a Term,
+    replacement_vars: HashSet<Symbol>,
+    /// Names that fresh variables must avoid, only computed once needed.
+    used_vars: Option<HashSet<Symbol>>,
+    counters: HashMap<Symbol, usize>,
+    /// Names of the enclosing binders in the result, by their original name.
+    binders: HashMap<Symbol, Vec<Symbol>>,
+}
+
+impl<'a> Fold<&'a Term> for Substitution<'a> {
+    /// The original and the new name of the parameter.
+    type Binder = (Symbol, Symbol);
+    type Output = Term;
+
+    fn visit(&mut self, term: &'a Term) -> Visit<&'a Term, (Symbol, Symbol), Term> {
+        match term {
+            Term::Var(x) => Visit::Done(match self.binders.get(x).and_then(|names| names.last()) {
+                Some(name) => Term::Var(*name),
+                None if *x == self.var => self.replacement.clone(),
+                None => Term::Var(*x),
+            }),
+            Term::Abs(param, body) => {
+                // below a binder for `var` nothing is substituted, so nothing can be captured
+                let shadowed = self.binders.get(&self.var).is_some_and(|names| !names.is_empty());
+                let name = if !shadowed && *param != self.var && self.replacement_vars.contains(param) {
+                    // Prevent variable capture by renaming the parameter
+                    let (term, replacement) = (self.term, self.replacement);
+                    let used_vars = self.used_vars.get_or_insert_with(|| {
+                        collect_all_vars(term)
+                            .union(&collect_all_vars(replacement))
+                            .cloned()
+                            .collect()
+                    });
+                    let counter = self.counters.entry(*param).or_insert(1);
+                    let fresh_var = fresh_name(*param, used_vars, counter);
+                    used_vars.insert(fresh_var);
+                    fresh_var
+                } else {
+                    *param
+                };
+                self.binders.entry(*param).or_default().push(name);
+                Visit::Abs((*param, name), body)
+            }
+            Term::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+
+    fn abs(&mut self, (param, name): (Symbol, Symbol), body: Term) -> Term {
+        self.binders.get_mut(&param).and_then(|names| names.pop());
+        Term::Abs(name, Box::new(body))
+    }
+
+    fn app(&mut self, t1: Term, t2: Term) -> Term {
+        Term::App(Box::new(t1), Box::new(t2))
+    }
+}
+
+/// Reduces all η-redexes `λx. M x`, where `x` is not free in `M`, to `M`.
+///
+/// Subterms are reduced first, so η-redexes that only arise from other
+/// reductions are reduced as well, e.g. `λx. λy. f x y` reduces to `f`.
+pub fn eta_reduce(term: &Term) -> Term {
+    struct EtaReduce;
+
+    impl<'a> Fold<&'a Term> for EtaReduce {
+        type Binder = Symbol;
+        type Output = Term;
+
+        fn visit(&mut self, term: &'a Term) -> Visit<&'a Term, Symbol, Term> {
+            Visit::subterms(term)
+        }
+
+        fn abs(&mut self, param: Symbol, mut body: Term) -> Term {
+            match &body {
+                Term::App(function, arg)
+                    if **arg == Term::Var(param) && !free_variables(function).contains(&param) =>
+                {
+                    let mut children = Vec::with_capacity(2);
+                    take_children(&mut body, &mut children);
+                    children.swap_remove(0)
+                }
+                _ => Term::Abs(param, Box::new(body)),
+            }
+        }
+
+        fn app(&mut self, t1: Term, t2: Term) -> Term {
+            Term::App(Box::new(t1), Box::new(t2))
+        }
+    }
+
+    fold(term, &mut EtaReduce)
+}
+
+/// η-expands a term `M` to `λx. M x`, where `x` is a variable not free in `M`.
+///
+/// Example: `f` expands to `λx. f x`, and `x` to `λx_1. x x_1`.
+pub fn eta_expand(term: &Term) -> Term {
     let mut counter = 1;
-    while all_vars.contains(&fresh_var) {
-        fresh_var = format!("{}_{}", base_name, counter);
-        counter += 1;
+    let param = fresh_name(Symbol::intern("x"), &free_variables(term), &mut counter);
+    Term::Abs(param, Box::new(app(term.clone(), Term::Var(param))))
+}
+
+/// Collects free variables in a term.
+pub fn free_variables(term: &Term) -> HashSet<Symbol> {
+    enum Task<'a> {
+        Visit(&'a Term),
+        Unbind(Symbol),
+    }
+
+    let mut free = HashSet::new();
+    // number of enclosing binders for each name
+    let mut bound: HashMap<Symbol, usize> = HashMap::new();
+    let mut tasks = vec![Task::Visit(term)];
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(Term::Var(x)) => {
+                if !bound.contains_key(x) {
+                    free.insert(*x);
+                }
+            }
+            Task::Visit(Term::Abs(param, body)) => {
+                *bound.entry(*param).or_default() += 1;
+                tasks.push(Task::Unbind(*param));
+                tasks.push(Task::Visit(body));
+            }
+            Task::Visit(Term::App(t1, t2)) => {
+                tasks.push(Task::Visit(t2));
+                tasks.push(Task::Visit(t1));
+            }
+            Task::Unbind(param) => {
+                if let Some(count) = bound.get_mut(&param) {
+                    *count -= 1;
+                    if *count == 0 {
+                        bound.remove(&param);
+                    }
+                }
+            }
+        }
+    }
+    free
+}
+
+/// Generates a fresh variable name based on `base_name` that doesn't exist in `existing_vars`.
+/// Numbered candidates start at `counter`, which is advanced past the returned name,
+/// so that repeated renaming of the same name doesn't retry taken names.
+fn fresh_name(base_name: Symbol, existing_vars: &HashSet<Symbol>, counter: &mut usize) -> Symbol {
+    let mut fresh_var = base_name;
+    while existing_vars.contains(&fresh_var) {
+        fresh_var = base_name.numbered(*counter);
+        *counter += 1;
     }
     fresh_var
 }
 
 /// Collects all variables in a term (free and bound).
-fn collect_all_vars(term: &Term) -> HashSet<String> {
-    match term {
-        Term::Var(x) => {
-            let mut vars = HashSet::new();
-            vars.insert(x.clone());
-            vars
-        }
-        Term::Abs(param, body) => {
-            let mut vars = collect_all_vars(body);
-            vars.insert(param.clone());
-            vars
-        }
-        Term::App(t1, t2) => {
-            let mut vars = collect_all_vars(t1);
-            vars.extend(collect_all_vars(t2));
-            vars
+fn collect_all_vars(term: &Term) -> HashSet<Symbol> {
+    let mut vars = HashSet::new();
+    let mut stack = vec![term];
+    while let Some(term) = stack.pop() {
+        match term {
+            Term::Var(x) => {
+                vars.insert(*x);
+            }
+            Term::Abs(param, body) => {
+                vars.insert(*param);
+                stack.push(body);
+            }
+            Term::App(t1, t2) => {
+                stack.push(t2);
+                stack.push(t1);
+            }
         }
     }
+    vars
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::assert_alpha_eq;
+    use crate::parser::parse;
 
     #[test]
     fn test_free_variables() {
         let term = abs("x", app(var("x"), var("y")));
         let free_vars = free_variables(&term);
-        let expected_vars: HashSet<_> = vec!["y".to_string()].into_iter().collect();
+        let expected_vars: HashSet<_> = vec![Symbol::intern("y")].into_iter().collect();
         assert_eq!(free_vars, expected_vars);
     }
 
@@ -144,8 +846,9 @@ mod tests {
         let term = abs("x", var("y"));
         let replacement = var("x");
         let substituted = substitute(&term, "y", &replacement);
-        let expected = abs("x_1", var("x"));
-        assert_eq!(substituted, expected);
+        // the binder is renamed, whatever the fresh name is
+        let expected = abs("z", var("x"));
+        assert_alpha_eq!(substituted, expected);
     }
 
     #[test]
@@ -170,7 +873,7 @@ mod tests {
     fn test_free_variables_in_nested_abstraction() {
         let term = abs("x", abs("y", app(var("x"), var("z"))));
         let free_vars = free_variables(&term);
-        let expected_vars: HashSet<_> = vec!["z".to_string()].into_iter().collect();
+        let expected_vars: HashSet<_> = vec![Symbol::intern("z")].into_iter().collect();
         assert_eq!(free_vars, expected_vars);
     }
 
@@ -190,4 +893,453 @@ mod tests {
         let expected = var("z");
         assert_eq!(evaluated, expected);
     }
+
+    #[test]
+    fn test_eval_left_nested_application() {
+        // ((λx. x) y) z -> y z
+        let term = app(app(abs("x", var("x")), var("y")), var("z"));
+        assert_eq!(eval(&term), app(var("y"), var("z")));
+    }
+
+    #[test]
+    fn test_eval_normal_order_discards_divergent_argument() {
+        // (λx. y) ((λx. x x) (λx. x x)) -> y
+        let omega = app(
+            abs("x", app(var("x"), var("x"))),
+            abs("x", app(var("x"), var("x"))),
+        );
+        let term = app(abs("x", var("y")), omega);
+        assert_eq!(eval_with(&term, Strategy::NormalOrder), var("y"));
+        assert_eq!(eval_with(&term, Strategy::CallByName), var("y"));
+    }
+
+    #[test]
+    fn test_eval_weak_strategies_stop_at_abstractions() {
+        // λx. (λy. y) x
+        let term = abs("x", app(abs("y", var("y")), var("x")));
+        assert_eq!(eval_with(&term, Strategy::CallByName), term);
+        assert_eq!(eval_with(&term, Strategy::CallByValue), term);
+        assert_eq!(eval_with(&term, Strategy::NormalOrder), abs("x", var("x")));
+        assert_eq!(eval_with(&term, Strategy::ApplicativeOrder), abs("x", var("x")));
+        assert_eq!(eval_with(&term, Strategy::HeadReduction), abs("x", var("x")));
+    }
+
+    #[test]
+    fn test_eval_call_by_value_evaluates_arguments() {
+        // (λx. λy. x) ((λz. z) w)
+        let term = app(
+            abs("x", abs("y", var("x"))),
+            app(abs("z", var("z")), var("w")),
+        );
+        assert_eq!(eval_with(&term, Strategy::CallByValue), abs("y", var("w")));
+        assert_eq!(
+            eval_with(&term, Strategy::CallByName),
+            abs("y", app(abs("z", var("z")), var("w")))
+        );
+    }
+
+    #[test]
+    fn test_eval_head_reduction_leaves_arguments() {
+        // λx. x ((λy. y) z)
+        let term = abs("x", app(var("x"), app(abs("y", var("y")), var("z"))));
+        assert_eq!(eval_with(&term, Strategy::HeadReduction), term);
+        assert_eq!(eval(&term), abs("x", app(var("x"), var("z"))));
+    }
+
+    #[test]
+    fn test_eval_church_addition() {
+        // plus 1 2 = 3 with Church numerals
+        let numeral = |n: usize| {
+            let mut body = var("x");
+            for _ in 0..n {
+                body = app(var("f"), body);
+            }
+            abs("f", abs("x", body))
+        };
+        let plus = abs(
+            "m",
+            abs(
+                "n",
+                abs(
+                    "f",
+                    abs(
+                        "x",
+                        app(
+                            app(var("m"), var("f")),
+                            app(app(var("n"), var("f")), var("x")),
+                        ),
+                    ),
+                ),
+            ),
+        );
+        let term = app(app(plus, numeral(1)), numeral(2));
+        assert_eq!(eval_with(&term, Strategy::NormalOrder), numeral(3));
+        assert_eq!(eval_with(&term, Strategy::ApplicativeOrder), numeral(3));
+    }
+
+    #[test]
+    fn test_step_single_reduction() {
+        // (λx. x) ((λy. y) z) -> (λy. y) z -> z
+        let term = app(abs("x", var("x")), app(abs("y", var("y")), var("z")));
+        let once = step(&term, Strategy::NormalOrder);
+        assert_eq!(once, Some(app(abs("y", var("y")), var("z"))));
+        let twice = step(&once.unwrap(), Strategy::NormalOrder);
+        assert_eq!(twice, Some(var("z")));
+        assert_eq!(step(&var("z"), Strategy::NormalOrder), None);
+    }
+
+    #[test]
+    fn test_step_applicative_order_reduces_argument_first() {
+        // (λx. x) ((λy. y) z) -> (λx. x) z
+        let term = app(abs("x", var("x")), app(abs("y", var("y")), var("z")));
+        assert_eq!(
+            find_redex(&term, Strategy::ApplicativeOrder),
+            Some(vec![Direction::Right])
+        );
+        assert_eq!(
+            step(&term, Strategy::ApplicativeOrder),
+            Some(app(abs("x", var("x")), var("z")))
+        );
+    }
+
+    #[test]
+    fn test_step_weak_strategies_stop_at_abstraction() {
+        let term = abs("x", app(abs("y", var("y")), var("x")));
+        assert_eq!(step(&term, Strategy::CallByName), None);
+        assert_eq!(step(&term, Strategy::CallByValue), None);
+        assert_eq!(find_redex(&term, Strategy::HeadReduction), Some(vec![Direction::Body]));
+    }
+
+    #[test]
+    fn test_trace_records_redex_positions() {
+        // x ((λy. y) z) ((λw. w) z) reduces both arguments from left to right
+        let term = app(
+            app(var("x"), app(abs("y", var("y")), var("z"))),
+            app(abs("w", var("w")), var("z")),
+        );
+        let steps: Vec<_> = trace(&term, Strategy::NormalOrder).collect();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].redex, vec![Direction::Left, Direction::Right]);
+        assert_eq!(steps[1].redex, vec![Direction::Right]);
+        assert_eq!(steps[1].term, app(app(var("x"), var("z")), var("z")));
+    }
+
+    #[test]
+    fn test_trace_agrees_with_eval() {
+        let term = app(
+            app(abs("x", abs("y", app(var("y"), var("x")))), var("a")),
+            abs("z", var("z")),
+        );
+        for strategy in Strategy::ALL {
+            let last = trace(&term, strategy).last().map(|s| s.term);
+            assert_eq!(last.unwrap_or(term.clone()), eval_with(&term, strategy));
+        }
+    }
+
+    #[test]
+    fn test_find_redex_resumes_after_contracted_redex() {
+        let sources = [
+            "(λx. x) ((λy. y) z)",
+            "(λx. λy. x) ((λx. x) a) b",
+            "(λm n f x. m f (n f x)) (λf x. f x) (λf x. f (f x))",
+            "x ((λy. λz. y z) w) ((λw. w) ((λv. v) u))",
+            "λa. (λx. x) (λy. (λz. z) y) ((λw. w w) b)",
+        ];
+        for source in sources {
+            let term = parse(source).unwrap();
+            for strategy in Strategy::ALL {
+                for step in trace(&term, strategy) {
+                    let from = resume_point(step.redex);
+                    assert_eq!(
+                        find_redex_from(&step.term, strategy, &from),
+                        find_redex(&step.term, strategy),
+                        "resuming at {} in `{}` with {strategy}",
+                        format_path(&from),
+                        step.term
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_subterm_at() {
+        let term = abs("x", app(var("x"), var("y")));
+        let path = [Direction::Body, Direction::Right];
+        assert_eq!(subterm_at(&term, &path), Some(&var("y")));
+        assert_eq!(subterm_at(&term, &[Direction::Left]), None);
+    }
+
+    #[test]
+    fn test_redexes() {
+        let term = app(abs("x", var("x")), app(abs("y", var("y")), var("z")));
+        assert_eq!(redexes(&term), vec![vec![], vec![Direction::Right]]);
+        assert!(redexes(&var("x")).is_empty());
+
+        // λx. (λy. y) x ((λz. z) x)
+        let term = abs(
+            "x",
+            app(
+                app(abs("y", var("y")), var("x")),
+                app(abs("z", var("z")), var("x")),
+            ),
+        );
+        assert_eq!(
+            redexes(&term),
+            vec![
+                vec![Direction::Body, Direction::Left],
+                vec![Direction::Body, Direction::Right]
+            ]
+        );
+        // the first redex is the one normal order contracts
+        assert_eq!(find_redex(&term, Strategy::NormalOrder), Some(redexes(&term)[0].clone()));
+    }
+
+    #[test]
+    fn test_reduce_at() {
+        let term = app(abs("x", var("x")), app(abs("y", var("y")), var("z")));
+        assert_eq!(reduce_at(&term, &[]), Some(app(abs("y", var("y")), var("z"))));
+        assert_eq!(reduce_at(&term, &[Direction::Right]), Some(app(abs("x", var("x")), var("z"))));
+        assert_eq!(reduce_at(&term, &[Direction::Left]), None);
+        assert_eq!(reduce_at(&term, &[Direction::Body]), None);
+    }
+
+    #[test]
+    fn test_eval_limited_runs_out_of_fuel() {
+        // (λx. x x) (λx. x x) reduces to itself forever
+        let omega = app(
+            abs("x", app(var("x"), var("x"))),
+            abs("x", app(var("x"), var("x"))),
+        );
+        let result = eval_limited(&omega, Strategy::NormalOrder, Limits::steps(10));
+        assert_eq!(
+            result,
+            Err(EvalError::OutOfFuel {
+                partial: omega.clone(),
+                steps: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_eval_limited_term_too_large() {
+        // (λx. x x x) (λx. x x x) grows with every step
+        let delta3 = abs("x", app(app(var("x"), var("x")), var("x")));
+        let term = app(delta3.clone(), delta3);
+        let limits = Limits {
+            max_steps: 1_000,
+            max_size: Some(50),
+        };
+        match eval_limited(&term, Strategy::NormalOrder, limits) {
+            Err(EvalError::TermTooLarge { partial, size, .. }) => {
+                assert!(size > 50);
+                assert_eq!(partial.size(), size);
+            }
+            other => panic!("expected TermTooLarge, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_eval_limited_within_budget() {
+        let term = app(abs("x", var("x")), app(abs("y", var("y")), var("z")));
+        assert_eq!(
+            eval_limited(&term, Strategy::NormalOrder, Limits::steps(2)),
+            Ok(var("z"))
+        );
+        assert!(eval_limited(&term, Strategy::NormalOrder, Limits::steps(1)).is_err());
+    }
+
+    /// `f x x … x` with `n` arguments.
+    fn long_spine(f: &str, n: usize) -> Term {
+        let mut term = var(f);
+        for _ in 0..n {
+            term = app(term, var("x"));
+        }
+        term
+    }
+
+    /// `λx. λx. … λx. body` with `n` binders.
+    fn deep_abstraction(n: usize, body: Term) -> Term {
+        let mut term = body;
+        for _ in 0..n {
+            term = abs("x", term);
+        }
+        term
+    }
+
+    #[test]
+    fn test_eval_long_application_spine_is_stack_safe() {
+        // (λy. y x … x) (λz. z) -> x x … x
+        let term = app(abs("y", long_spine("y", 1_000_000)), abs("z", var("z")));
+        let evaluated = eval(&term);
+        assert_eq!(evaluated.size(), 2 * 1_000_000 - 1);
+        assert_eq!(evaluated, long_spine("x", 999_999));
+    }
+
+    #[test]
+    fn test_substitute_deep_term_is_stack_safe() {
+        // λx. … λx. y, substituting y := x renames all binders
+        let term = deep_abstraction(200_000, var("y"));
+        assert_eq!(free_variables(&term), HashSet::from([Symbol::intern("y")]));
+        let substituted = substitute(&term, "y", &var("x"));
+        assert_eq!(free_variables(&substituted), HashSet::from([Symbol::intern("x")]));
+        assert_eq!(substituted.clone().size(), term.size());
+    }
+
+    #[test]
+    fn test_substitute_respects_shadowing() {
+        // (y (λy. y)) [y := z] leaves bound occurrences alone
+        let term = app(var("y"), abs("y", var("y")));
+        let substituted = substitute(&term, "y", &var("z"));
+        assert_eq!(substituted, app(var("z"), abs("y", var("y"))));
+    }
+
+    #[test]
+    fn test_eta_reduce() {
+        assert_eq!(eta_reduce(&abs("x", app(var("f"), var("x")))), var("f"));
+        // cascading reductions
+        let term = abs("x", abs("y", app(app(var("f"), var("x")), var("y"))));
+        assert_eq!(eta_reduce(&term), var("f"));
+        // `x` occurs free in the function
+        let term = abs("x", app(var("x"), var("x")));
+        assert_eq!(eta_reduce(&term), term);
+        let term = abs("x", app(app(var("f"), var("x")), var("x")));
+        assert_eq!(eta_reduce(&term), term);
+        // nested redexes are reduced as well
+        let term = app(var("g"), abs("y", app(var("h"), var("y"))));
+        assert_eq!(eta_reduce(&term), app(var("g"), var("h")));
+    }
+
+    #[test]
+    fn test_eta_expand() {
+        assert_eq!(eta_expand(&var("f")), abs("x", app(var("f"), var("x"))));
+        assert_eq!(eta_expand(&var("x")), abs("x_1", app(var("x"), var("x_1"))));
+        let term = abs("y", app(var("x"), var("y")));
+        assert_eq!(eta_reduce(&eta_expand(&term)), var("x"));
+    }
+
+    #[test]
+    fn test_eval_beta_eta() {
+        let term = abs("x", app(abs("y", app(var("f"), var("y"))), var("x")));
+        assert_eq!(eval(&term), abs("x", app(var("f"), var("x"))));
+        assert_eq!(eval_beta_eta(&term), var("f"));
+        // Church numeral one is η-equivalent to the identity
+        let one = abs("f", abs("x", app(var("f"), var("x"))));
+        assert_eq!(eval_beta_eta(&one), abs("f", var("f")));
+    }
+
+    #[test]
+    fn test_strategy_names() {
+        for strategy in Strategy::ALL {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        assert_eq!("call-by-value".parse(), Ok(Strategy::CallByValue));
+        assert!("lazy".parse::<Strategy>().is_err());
+    }
+
+    #[test]
+    fn test_backend_names() {
+        for backend in Backend::ALL {
+            assert_eq!(backend.to_string().parse(), Ok(backend));
+        }
+        assert!("ssa".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn test_eval_on_backends() {
+        let sources = [
+            "x",
+            "λx. x",
+            "(λx. x) y",
+            "(λx. λy. x) y",
+            "(λx. λy. x) a b",
+            "(λx. x) ((λy. y) z)",
+            "λx. (λy. y) x",
+            "λy. (λx. λy. x) y",
+            "(λx. λy. x) ((λz. z) w)",
+            "(λx. λy. y x) ((λz. z) w)",
+            "x ((λy. y) z) ((λy. y) w)",
+            "(λf. f a) (λx. (λy. y) x)",
+            "(λx. λy. x y) (λz. y)",
+            "(λm n f x. m f (n f x)) (λf x. f x) (λf x. f (f x)) g a",
+            "(λf. λx. f (f x)) (λf. λx. f (f x)) g a",
+        ];
+        for source in sources {
+            let term = parse(source).unwrap();
+            for strategy in Strategy::ALL {
+                let expected = eval_with(&term, strategy);
+                for backend in Backend::ALL {
+                    let result = eval_on(&term, strategy, Limits::default(), backend);
+                    if backend.supports(strategy) {
+                        assert_alpha_eq!(result.unwrap(), expected, "evaluating `{source}` with {strategy} on {backend}");
+                    } else {
+                        assert_eq!(result, Err(EvalError::Unsupported { backend, strategy }));
+                    }
+                }
+            }
+        }
+    }
+
+    /// The supported pairs of back ends and strategies.
+    fn supported() -> impl Iterator<Item = (Backend, Strategy)> {
+        Backend::ALL
+            .into_iter()
+            .flat_map(|backend| Strategy::ALL.map(|strategy| (backend, strategy)))
+            .filter(|(backend, strategy)| backend.supports(*strategy))
+    }
+
+    #[test]
+    fn test_eval_on_backends_runs_out_of_fuel() {
+        let omega = parse("(λx. x x) (λx. x x)").unwrap();
+        let discard = parse("(λx. z) ((λx. x x) (λx. x x))").unwrap();
+        for (backend, strategy) in supported() {
+            // strict strategies also loop on the discarded argument
+            let strict = matches!(strategy, Strategy::CallByValue | Strategy::ApplicativeOrder);
+            let terms = if strict { vec![&omega, &discard] } else { vec![&omega] };
+            for term in terms {
+                let result = eval_on(term, strategy, Limits::steps(10), backend);
+                let Err(EvalError::OutOfFuel { partial, steps }) = result else {
+                    panic!("expected {backend} to run out of fuel with {strategy}");
+                };
+                assert_eq!(steps, 10);
+                assert_alpha_eq!(partial, term.clone(), "partial result of {backend} with {strategy}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_on_backends_check_the_size_limit() {
+        // (λx. x x x) (λx. x x x) grows by every step
+        let term = parse("(λx. x x x) (λx. x x x)").unwrap();
+        let limits = Limits {
+            max_steps: 20,
+            max_size: Some(50),
+        };
+        for (backend, strategy) in supported() {
+            let result = eval_on(&term, strategy, limits, backend);
+            let Err(EvalError::TermTooLarge { partial, size, .. }) = result else {
+                panic!("expected {backend} to exceed the size limit with {strategy}");
+            };
+            assert!(size > 50);
+            assert_eq!(partial.size(), size);
+        }
+    }
+
+    #[test]
+    fn test_eval_on_backends_deep_terms_are_stack_safe() {
+        // (λx. x) y y … y, a long application spine
+        let mut spine = abs("x", var("x"));
+        // f (f (… (f ((λx. x) y)))), a deeply nested argument
+        let mut nested = app(abs("x", var("x")), var("y"));
+        for _ in 0..100_000 {
+            spine = app(spine, var("y"));
+            nested = app(var("f"), nested);
+        }
+        for term in [spine, nested] {
+            for (backend, strategy) in supported() {
+                let result = eval_on(&term, strategy, Limits::default(), backend).unwrap();
+                assert!(result == eval_with(&term, strategy), "evaluating with {strategy} on {backend}");
+            }
+        }
+    }
 }
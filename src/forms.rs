@@ -0,0 +1,123 @@
+use crate::eval::*;
+use crate::term::*;
+use std::fmt;
+
+/// The most specific kind of normal form a term is in, see [`classify`].
+///
+/// Every normal form is a head normal form, and every head normal form is a
+/// weak head normal form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    /// No redex at all, e.g. `λx. x y`.
+    NormalForm,
+    /// `λx1. … λxn. y M1 … Mk` with some redex in the arguments,
+    /// e.g. `λx. x ((λy. y) x)`.
+    HeadNormalForm,
+    /// An abstraction whose body has a head redex, e.g. `λx. (λy. y) x`.
+    WeakHeadNormalForm,
+    /// An abstraction applied to an argument at the head, e.g. `(λx. x) y z`,
+    /// which no strategy would stop at.
+    Reducible,
+}
+
+impl fmt::Display for Form {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Form::NormalForm => write!(f, "normal form"),
+            Form::HeadNormalForm => write!(f, "head normal form"),
+            Form::WeakHeadNormalForm => write!(f, "weak head normal form"),
+            Form::Reducible => write!(f, "not in weak head normal form"),
+        }
+    }
+}
+
+/// Classifies a term by the most specific normal form it is in.
+///
+/// Examples:
+///   `x (λy. y)` is a normal form.
+///   `λx. x ((λy. y) x)` is a head normal form.
+///   `λx. (λy. y) x` is a weak head normal form.
+///   `(λx. x) y` is none of them.
+pub fn classify(term: &Term) -> Form {
+    if is_normal_form(term) {
+        Form::NormalForm
+    } else if is_head_normal_form(term) {
+        Form::HeadNormalForm
+    } else if is_weak_head_normal_form(term) {
+        Form::WeakHeadNormalForm
+    } else {
+        Form::Reducible
+    }
+}
+
+/// Checks whether a term contains no β-redex.
+pub fn is_normal_form(term: &Term) -> bool {
+    find_redex(term, Strategy::NormalOrder).is_none()
+}
+
+/// Checks whether a term has the form `λx1. … λxn. y M1 … Mk`, with `n, k ≥ 0`,
+/// i.e. whether its head is a variable.
+pub fn is_head_normal_form(term: &Term) -> bool {
+    let mut current = term;
+    while let Term::Abs(_, body) = current {
+        current = body;
+    }
+    has_variable_head(current)
+}
+
+/// Checks whether a term is an abstraction or has the form `y M1 … Mk`.
+pub fn is_weak_head_normal_form(term: &Term) -> bool {
+    matches!(term, Term::Abs(_, _)) || has_variable_head(term)
+}
+
+/// Checks whether the leftmost term of an application spine is a variable.
+fn has_variable_head(term: &Term) -> bool {
+    let mut current = term;
+    while let Term::App(t1, _) = current {
+        current = t1;
+    }
+    matches!(current, Term::Var(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn form(source: &str) -> Form {
+        classify(&parse(source).unwrap())
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(form("x"), Form::NormalForm);
+        assert_eq!(form("x (λy. y)"), Form::NormalForm);
+        assert_eq!(form("λx. x ((λy. y) x)"), Form::HeadNormalForm);
+        assert_eq!(form("x ((λy. y) z)"), Form::HeadNormalForm);
+        assert_eq!(form("λx. (λy. y) x"), Form::WeakHeadNormalForm);
+        assert_eq!(form("(λx. x) y z"), Form::Reducible);
+    }
+
+    #[test]
+    fn test_forms_are_nested() {
+        let term = parse("λx. x (λy. y)").unwrap();
+        assert!(is_normal_form(&term));
+        assert!(is_head_normal_form(&term));
+        assert!(is_weak_head_normal_form(&term));
+
+        let term = parse("λx. (λy. y) x").unwrap();
+        assert!(!is_normal_form(&term));
+        assert!(!is_head_normal_form(&term));
+        assert!(is_weak_head_normal_form(&term));
+    }
+
+    #[test]
+    fn test_results_of_strategies() {
+        let term = parse("(λx. λy. y x) ((λz. z) w)").unwrap();
+        assert_eq!(classify(&eval_with(&term, Strategy::NormalOrder)), Form::NormalForm);
+        assert_eq!(classify(&eval_with(&term, Strategy::HeadReduction)), Form::HeadNormalForm);
+
+        let term = parse("(λx. λy. x) ((λz. z) w)").unwrap();
+        assert_eq!(classify(&eval_with(&term, Strategy::CallByName)), Form::WeakHeadNormalForm);
+    }
+}
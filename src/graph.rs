@@ -0,0 +1,222 @@
+use crate::alpha::*;
+use crate::eval::*;
+use crate::term::*;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Bounds for [`reduction_graph`], which is infinite for many terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphLimits {
+    /// Maximum number of reduction steps from the start term.
+    pub max_depth: usize,
+    /// Maximum number of distinct terms in the graph.
+    pub max_terms: usize,
+    /// Maximum size (number of nodes) of a term in the graph, if any.
+    pub max_size: Option<usize>,
+}
+
+impl Default for GraphLimits {
+    fn default() -> Self {
+        GraphLimits {
+            max_depth: 10,
+            max_terms: 100,
+            max_size: Some(1000),
+        }
+    }
+}
+
+/// A reduction step in a [`ReductionGraph`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    /// Index of the term that is reduced.
+    pub from: usize,
+    /// Index of the resulting term.
+    pub to: usize,
+    /// Position of the contracted redex in the term `from`.
+    pub redex: Vec<Direction>,
+}
+
+/// The terms reachable from a term by contracting any redexes, with one
+/// edge per reduction step. Terms are identified up to α-equivalence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReductionGraph {
+    /// The distinct terms, the start term first, in order of discovery.
+    pub terms: Vec<Term>,
+    pub edges: Vec<Edge>,
+    /// Whether some reductions were not followed because of the limits.
+    pub truncated: bool,
+}
+
+/// Computes the reduction graph of `term` by breadth-first search,
+/// stopping at the given `limits`.
+///
+/// Example: `(λx. x) ((λy. y) z)` has the terms `(λx. x) ((λy. y) z)`,
+/// `(λy. y) z` and `z`, with edges for both redexes of the start term
+/// leading to `(λy. y) z`, as `(λx. x) z` is the same term up to renaming.
+pub fn reduction_graph(term: &Term, limits: GraphLimits) -> ReductionGraph {
+    let mut graph = ReductionGraph {
+        terms: vec![term.clone()],
+        edges: Vec::new(),
+        truncated: false,
+    };
+    let mut indices: HashMap<Alpha, usize> = HashMap::from([(Alpha(term.clone()), 0)]);
+    let mut queue = VecDeque::from([(0, 0)]);
+    while let Some((from, depth)) = queue.pop_front() {
+        let paths = redexes(&graph.terms[from]);
+        if depth == limits.max_depth {
+            graph.truncated |= !paths.is_empty();
+            continue;
+        }
+        for redex in paths {
+            let next = reduce_at(&graph.terms[from], &redex).expect("path points to a redex");
+            if limits.max_size.is_some_and(|max_size| next.size() > max_size) {
+                graph.truncated = true;
+                continue;
+            }
+            let key = Alpha(next);
+            let to = match indices.get(&key) {
+                Some(&to) => to,
+                None if graph.terms.len() == limits.max_terms => {
+                    graph.truncated = true;
+                    continue;
+                }
+                None => {
+                    let to = graph.terms.len();
+                    graph.terms.push(key.0.clone());
+                    indices.insert(key, to);
+                    queue.push_back((to, depth + 1));
+                    to
+                }
+            };
+            graph.edges.push(Edge { from, to, redex });
+        }
+    }
+    graph
+}
+
+impl ReductionGraph {
+    /// Indices of the terms in β-normal form.
+    ///
+    /// By the Church–Rosser theorem, a complete graph has at most one.
+    pub fn normal_forms(&self) -> Vec<usize> {
+        (0..self.terms.len())
+            .filter(|&i| redexes(&self.terms[i]).is_empty())
+            .collect()
+    }
+
+    /// The indices of the terms visited by `strategy`, starting with the start
+    /// term. The path ends when the strategy stops, leaves the explored part of
+    /// the graph, or returns to a term it visited before, in which case that
+    /// term is repeated at the end: the strategy diverges.
+    pub fn strategy_path(&self, strategy: Strategy) -> Vec<usize> {
+        let mut path = vec![0];
+        let mut current = 0;
+        while let Some(redex) = find_redex(&self.terms[current], strategy) {
+            let Some(edge) = self
+                .edges
+                .iter()
+                .find(|edge| edge.from == current && edge.redex == redex)
+            else {
+                break;
+            };
+            current = edge.to;
+            let cycle = path.contains(&current);
+            path.push(current);
+            if cycle {
+                break;
+            }
+        }
+        path
+    }
+}
+
+/// Lists the terms, marking normal forms, followed by the edges with the
+/// positions of their redexes.
+impl fmt::Display for ReductionGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if redexes(term).is_empty() {
+                writeln!(f, "{}: {} (normal form)", i, term)?;
+            } else {
+                writeln!(f, "{}: {}", i, term)?;
+            }
+        }
+        for edge in &self.edges {
+            writeln!(f, "{} -> {} at {}", edge.from, edge.to, format_path(&edge.redex))?;
+        }
+        if self.truncated {
+            writeln!(f, "(incomplete, the limits were reached)")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn graph(source: &str) -> ReductionGraph {
+        reduction_graph(&parse(source).unwrap(), GraphLimits::default())
+    }
+
+    #[test]
+    fn test_reduction_graph_diamond() {
+        let graph = graph("(λx. x x) ((λy. y) z)");
+        assert_eq!(graph.terms.len(), 6);
+        assert_eq!(graph.edges.len(), 7);
+        assert!(!graph.truncated);
+        // all ways lead to the same normal form
+        assert_eq!(graph.normal_forms(), vec![5]);
+        assert_eq!(graph.terms[5], app(var("z"), var("z")));
+        assert_eq!(graph.strategy_path(Strategy::NormalOrder), vec![0, 1, 3, 5]);
+        assert_eq!(graph.strategy_path(Strategy::ApplicativeOrder), vec![0, 2, 5]);
+        // call by name stops at the weak head normal form z ((λy. y) z)
+        assert_eq!(graph.strategy_path(Strategy::CallByName), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn test_reduction_graph_identifies_alpha_equivalent_terms() {
+        // reduces to (λy. y y) (λy. y y), which is the start term up to renaming
+        let graph = graph("(λx. x x) (λy. y y)");
+        assert_eq!(graph.terms.len(), 1);
+        assert_eq!(graph.edges, vec![Edge { from: 0, to: 0, redex: vec![] }]);
+        assert!(graph.normal_forms().is_empty());
+    }
+
+    #[test]
+    fn test_strategy_paths() {
+        // normal order discards the divergent argument, applicative order loops
+        let graph = graph("(λx. z) ((λx. x x) (λx. x x))");
+        assert!(!graph.truncated);
+        let normal = graph.strategy_path(Strategy::NormalOrder);
+        assert_eq!(normal.len(), 2);
+        assert_eq!(graph.terms[normal[1]], var("z"));
+        assert_eq!(graph.strategy_path(Strategy::ApplicativeOrder), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_reduction_graph_limits() {
+        // (λx. x x x) (λx. x x x) grows forever
+        let term = parse("(λx. x x x) (λx. x x x)").unwrap();
+        let limits = GraphLimits {
+            max_depth: 3,
+            ..GraphLimits::default()
+        };
+        let graph = reduction_graph(&term, limits);
+        assert!(graph.truncated);
+        assert_eq!(graph.terms.len(), 4);
+
+        let limits = GraphLimits {
+            max_terms: 2,
+            ..GraphLimits::default()
+        };
+        assert_eq!(reduction_graph(&term, limits).terms.len(), 2);
+    }
+
+    #[test]
+    fn test_display() {
+        let graph = graph("(λx. x) y");
+        assert_eq!(graph.to_string(), "0: (λx. x) y\n1: y (normal form)\n0 -> 1 at root\n");
+    }
+}
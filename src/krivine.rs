@@ -0,0 +1,201 @@
+//! A Krivine abstract machine for call-by-name evaluation.
+//!
+//! The machine works on de Bruijn terms and never substitutes: a variable is
+//! looked up in an environment of closures, i.e. unevaluated arguments paired
+//! with the environment they were created in, and an argument is only
+//! evaluated when it reaches the head position.
+//!
+//! The machine itself stops at weak head normal form. To compute head normal
+//! forms and normal forms, the result is read back into a term, evaluating
+//! further under abstractions and in arguments, which continues the machine
+//! with the bound variables represented by de Bruijn levels.
+
+use crate::debruijn::*;
+use crate::eval::*;
+use crate::machine::{self, Readback};
+use crate::term::*;
+
+type Frames<'a> = machine::Frames<Closure<'a>>;
+
+/// An argument waiting to be evaluated, or a variable bound by an abstraction
+/// that the readback went under.
+#[derive(Clone)]
+enum Closure<'a> {
+    Thunk(&'a DbTerm, Frames<'a>),
+    /// A bound variable, numbered from the outside in.
+    Level(usize),
+}
+
+/// Where the machine stopped.
+enum Stop<'a> {
+    /// At an abstraction without arguments.
+    Abs(Symbol, &'a DbTerm, Frames<'a>),
+    /// At a variable without value, applied to the closures on the stack:
+    /// a bound variable with its level, or a free variable with its index
+    /// among the free variables.
+    Level(usize),
+    Free(usize),
+    /// Out of fuel, at the given term.
+    OutOfFuel(&'a DbTerm, Frames<'a>),
+}
+
+/// The machine state besides the current term and environment.
+struct Machine<'a> {
+    /// Arguments of the current term, the first one last.
+    stack: Vec<Closure<'a>>,
+    steps: usize,
+    max_steps: usize,
+}
+
+impl<'a> Machine<'a> {
+    /// Runs the machine on `term` in `env` until it reaches weak head normal
+    /// form or runs out of fuel. Arguments that are left over stay on the stack.
+    fn run(&mut self, mut term: &'a DbTerm, mut env: Frames<'a>) -> Stop<'a> {
+        loop {
+            match term {
+                DbTerm::App(t1, t2) => {
+                    self.stack.push(Closure::Thunk(t2, env.clone()));
+                    term = t1;
+                }
+                DbTerm::Abs(hint, body) => {
+                    if self.stack.is_empty() {
+                        return Stop::Abs(*hint, body, env);
+                    }
+                    if self.steps == self.max_steps {
+                        return Stop::OutOfFuel(term, env);
+                    }
+                    let arg = self.stack.pop().expect("checked above");
+                    self.steps += 1;
+                    env = env.push(arg);
+                    term = body;
+                }
+                DbTerm::Var(index) => match env.get(*index) {
+                    Ok(Closure::Thunk(t, e)) => {
+                        let e = e.clone();
+                        term = t;
+                        env = e;
+                    }
+                    Ok(Closure::Level(level)) => return Stop::Level(*level),
+                    Err(depth) => return Stop::Free(index - depth),
+                },
+            }
+        }
+    }
+}
+
+/// Evaluates a term with the Krivine machine, see [`Backend::Krivine`].
+///
+/// Supports [`Strategy::CallByName`], which is what the machine computes,
+/// [`Strategy::HeadReduction`] and [`Strategy::NormalOrder`], which continue
+/// under abstractions and, for normal order, in the arguments of a variable.
+pub fn eval_krivine(term: &Term, strategy: Strategy, limits: Limits) -> Result<Term, EvalError> {
+    let (under_binders, arguments) = match strategy {
+        Strategy::NormalOrder => (true, true),
+        Strategy::HeadReduction => (true, false),
+        Strategy::CallByName => (false, false),
+        _ => {
+            return Err(EvalError::Unsupported {
+                backend: Backend::Krivine,
+                strategy,
+            })
+        }
+    };
+
+    enum Quote<'a> {
+        /// Evaluates the closure and reads back the result.
+        Eval(&'a DbTerm, Frames<'a>),
+        /// Reads back the closure without evaluating it.
+        Term(&'a DbTerm, Frames<'a>),
+    }
+
+    let (db, free) = DbTerm::from_term(term);
+    let mut machine = Machine {
+        stack: Vec::new(),
+        steps: 0,
+        max_steps: limits.max_steps,
+    };
+    let mut out_of_fuel = false;
+    let mut readback = Readback::new();
+    readback.push(Quote::Eval(&db, Frames::default()), 0);
+    readback.run(|readback, quote, depth| match quote {
+        Quote::Eval(t, env) if !out_of_fuel => {
+            let stop = machine.run(t, env);
+            let args = std::mem::take(&mut machine.stack);
+            match stop {
+                Stop::Abs(hint, body, env) => {
+                    let env = env.push(Closure::Level(depth));
+                    if under_binders {
+                        readback.push_abs(hint, Quote::Eval(body, env), depth);
+                    } else {
+                        readback.push_abs(hint, Quote::Term(body, env), depth);
+                    }
+                    return;
+                }
+                Stop::Level(level) => readback.level(level, depth),
+                Stop::Free(index) => readback.free(index, depth),
+                Stop::OutOfFuel(t, env) => {
+                    out_of_fuel = true;
+                    readback.push_app(args.len(), depth);
+                    push_args(readback, args, depth, Quote::Term);
+                    readback.push(Quote::Term(t, env), depth);
+                    return;
+                }
+            }
+            readback.push_app(args.len(), depth);
+            // only normal order evaluates the arguments of a variable
+            if arguments {
+                push_args(readback, args, depth, Quote::Eval);
+            } else {
+                push_args(readback, args, depth, Quote::Term);
+            }
+        }
+        Quote::Eval(t, env) | Quote::Term(t, env) => match t {
+            DbTerm::Var(index) => match env.get(*index) {
+                Ok(Closure::Thunk(t, e)) => readback.push(Quote::Term(t, e.clone()), depth),
+                Ok(Closure::Level(level)) => readback.level(*level, depth),
+                Err(env_depth) => readback.free(index - env_depth, depth),
+            },
+            DbTerm::Abs(hint, body) => {
+                readback.push_abs(*hint, Quote::Term(body, env.push(Closure::Level(depth))), depth);
+            }
+            DbTerm::App(t1, t2) => {
+                readback.push_app(1, depth);
+                readback.push(Quote::Term(t2, env.clone()), depth);
+                readback.push(Quote::Term(t1, env), depth);
+            }
+        },
+    });
+
+    machine::outcome(readback.finish().to_term(&free), machine.steps, out_of_fuel, limits)
+}
+
+/// Reads back the arguments on the machine stack, so that the first argument
+/// is processed first.
+fn push_args<'a, T>(
+    readback: &mut Readback<T>,
+    args: Vec<Closure<'a>>,
+    depth: usize,
+    quote: impl Fn(&'a DbTerm, Frames<'a>) -> T,
+) {
+    // the stack holds the first argument last, which is the order items are pushed in
+    for arg in args {
+        match arg {
+            Closure::Thunk(t, e) => readback.push(quote(t, e), depth),
+            Closure::Level(_) => unreachable!("levels are never arguments"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_krivine_is_lazy() {
+        // call by name leaves the argument untouched
+        let term = parse("(λx. λy. x) ((λz. z) w)").unwrap();
+        let result = eval_krivine(&term, Strategy::CallByName, Limits::default()).unwrap();
+        assert_eq!(result, parse("λy. (λz. z) w").unwrap());
+    }
+}
@@ -1,4 +1,18 @@
+pub mod symbol;
 pub mod term;
 pub mod pretty;
 pub mod eval;
 pub mod parser;
+pub mod env;
+pub mod repl;
+pub mod debruijn;
+pub mod alpha;
+pub mod arena;
+pub mod equiv;
+pub mod graph;
+pub mod dot;
+pub mod forms;
+pub mod krivine;
+pub mod cek;
+pub mod secd;
+mod machine;
@@ -0,0 +1,282 @@
+//! Parts shared by the abstract machines in [`crate::krivine`], [`crate::cek`]
+//! and [`crate::secd`]: environments, values of open terms and the readback
+//! of machine states into de Bruijn terms.
+
+use crate::debruijn::*;
+use crate::eval::*;
+use crate::term::*;
+use std::mem;
+use std::rc::Rc;
+
+/// The result of a machine that ran for `steps` steps, given the term read
+/// back from its final state: the size limit is checked on that term, as the
+/// machines have no intermediate terms, and then whether the fuel ran out.
+pub(crate) fn outcome(term: Term, steps: usize, out_of_fuel: bool, limits: Limits) -> Result<Term, EvalError> {
+    let size = term.size();
+    if limits.max_size.is_some_and(|max_size| size > max_size) {
+        Err(EvalError::TermTooLarge {
+            partial: term,
+            size,
+            steps,
+        })
+    } else if out_of_fuel {
+        Err(EvalError::OutOfFuel {
+            partial: term,
+            steps,
+        })
+    } else {
+        Ok(term)
+    }
+}
+
+/// An immutable, shared list of values for the variables of a de Bruijn
+/// term, indexed by de Bruijn index.
+pub(crate) struct Frames<T>(Option<Rc<Frame<T>>>);
+
+struct Frame<T> {
+    value: T,
+    next: Frames<T>,
+}
+
+impl<T> Frames<T> {
+    /// The frames with `value` for index 0, shifting all others by one.
+    pub(crate) fn push(&self, value: T) -> Frames<T> {
+        Frames(Some(Rc::new(Frame {
+            value,
+            next: self.clone(),
+        })))
+    }
+
+    /// The value for de Bruijn index `index`, or the number of values if the
+    /// variable is free in the frames.
+    pub(crate) fn get(&self, index: usize) -> Result<&T, usize> {
+        let mut frames = self;
+        let mut depth = 0;
+        while let Some(frame) = &frames.0 {
+            if depth == index {
+                return Ok(&frame.value);
+            }
+            frames = &frame.next;
+            depth += 1;
+        }
+        Err(depth)
+    }
+}
+
+impl<T> Clone for Frames<T> {
+    fn clone(&self) -> Self {
+        Frames(self.0.clone())
+    }
+}
+
+impl<T> Default for Frames<T> {
+    fn default() -> Self {
+        Frames(None)
+    }
+}
+
+impl<T> Drop for Frames<T> {
+    fn drop(&mut self) {
+        // unlink frames that are not shared one at a time, so that dropping
+        // a long list does not recurse
+        let mut next = self.0.take();
+        while let Some(frame) = next {
+            next = match Rc::try_unwrap(frame) {
+                Ok(mut frame) => frame.next.0.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+/// The value of a term under call by value, where `C` is the machine's
+/// representation of an abstraction with its environment.
+#[derive(Clone)]
+pub(crate) enum Value<C> {
+    Closure(C),
+    Neutral(Rc<Neutral<C>>),
+    /// A bound variable that the readback went under, numbered from the
+    /// outside in. Never seen by the machines themselves.
+    Level(usize),
+}
+
+/// A free variable, by its index among the free variables of the evaluated
+/// term, applied to values.
+#[derive(Clone)]
+pub(crate) struct Neutral<C> {
+    head: usize,
+    args: Vec<Value<C>>,
+}
+
+impl<C: Clone> Value<C> {
+    /// The free variable with the given index.
+    pub(crate) fn free(head: usize) -> Self {
+        Value::Neutral(Rc::new(Neutral {
+            head,
+            args: Vec::new(),
+        }))
+    }
+
+    /// Applies a neutral value to `arg`, giving a neutral value again.
+    pub(crate) fn apply_neutral(mut neutral: Rc<Neutral<C>>, arg: Value<C>) -> Self {
+        Rc::make_mut(&mut neutral).args.push(arg);
+        Value::Neutral(neutral)
+    }
+}
+
+impl<C> Drop for Neutral<C> {
+    fn drop(&mut self) {
+        // unnest neutral arguments, so that dropping e.g. the value of
+        // `x (x (… (x y)))` does not recurse
+        let mut pending = mem::take(&mut self.args);
+        while let Some(value) = pending.pop() {
+            if let Value::Neutral(neutral) = value {
+                if let Ok(mut neutral) = Rc::try_unwrap(neutral) {
+                    pending.append(&mut neutral.args);
+                }
+            }
+        }
+    }
+}
+
+/// A task of a [`Readback`], where `T` is what the machine reads back.
+enum Task<T> {
+    Quote(T),
+    /// Wraps the last result in an abstraction.
+    BuildAbs(Symbol),
+    /// Applies a result to the given number of results following it.
+    BuildApp(usize),
+}
+
+/// Turns machine states into a de Bruijn term with an explicit stack.
+///
+/// The machine pushes what it wants to read back with the number of
+/// enclosing abstractions of the result, which turns levels and free
+/// variables into indices, and expands each item into further tasks or
+/// results when [`Readback::run`] gets to it.
+pub(crate) struct Readback<T> {
+    tasks: Vec<(Task<T>, usize)>,
+    results: Vec<DbTerm>,
+}
+
+impl<T> Readback<T> {
+    pub(crate) fn new() -> Self {
+        Readback {
+            tasks: Vec::new(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Reads back `item` after the tasks pushed later.
+    pub(crate) fn push(&mut self, item: T, depth: usize) {
+        self.tasks.push((Task::Quote(item), depth));
+    }
+
+    /// Reads back an abstraction with the given body, whose depth is one more.
+    pub(crate) fn push_abs(&mut self, hint: Symbol, body: T, depth: usize) {
+        self.tasks.push((Task::BuildAbs(hint), depth));
+        self.tasks.push((Task::Quote(body), depth + 1));
+    }
+
+    /// Reads back the application of the next result to the `count` results
+    /// after it. The items for them have to be pushed next, last argument first.
+    pub(crate) fn push_app(&mut self, count: usize, depth: usize) {
+        self.tasks.push((Task::BuildApp(count), depth));
+    }
+
+    /// Adds a finished result.
+    pub(crate) fn result(&mut self, term: DbTerm) {
+        self.results.push(term);
+    }
+
+    /// Adds the variable with level `level` at `depth`.
+    pub(crate) fn level(&mut self, level: usize, depth: usize) {
+        self.results.push(DbTerm::Var(depth - level - 1));
+    }
+
+    /// Adds the free variable with index `index` at `depth`.
+    pub(crate) fn free(&mut self, index: usize, depth: usize) {
+        self.results.push(DbTerm::Var(depth + index));
+    }
+
+    /// Reads back a value: levels and neutral values right away, with `quote`
+    /// making items for the arguments. Closures are returned to the caller.
+    pub(crate) fn value<C: Clone>(
+        &mut self,
+        value: Value<C>,
+        depth: usize,
+        quote: impl Fn(Value<C>) -> T,
+    ) -> Option<C> {
+        match value {
+            Value::Closure(closure) => return Some(closure),
+            Value::Neutral(neutral) => {
+                self.free(neutral.head, depth);
+                self.push_app(neutral.args.len(), depth);
+                for arg in neutral.args.iter().rev() {
+                    self.push(quote(arg.clone()), depth);
+                }
+            }
+            Value::Level(level) => self.level(level, depth),
+        }
+        None
+    }
+
+    /// Runs all tasks, calling `expand` for each item.
+    pub(crate) fn run(&mut self, mut expand: impl FnMut(&mut Self, T, usize)) {
+        while let Some((task, depth)) = self.tasks.pop() {
+            match task {
+                Task::Quote(item) => expand(self, item, depth),
+                Task::BuildAbs(hint) => {
+                    let body = self.results.pop().expect("missing body");
+                    self.results.push(DbTerm::Abs(hint, Box::new(body)));
+                }
+                Task::BuildApp(count) => {
+                    let args = self.results.split_off(self.results.len() - count);
+                    let head = self.results.pop().expect("missing function");
+                    let result = args
+                        .into_iter()
+                        .fold(head, |t, arg| DbTerm::App(Box::new(t), Box::new(arg)));
+                    self.results.push(result);
+                }
+            }
+        }
+    }
+
+    /// The last result, once all tasks have run.
+    pub(crate) fn finish(&mut self) -> DbTerm {
+        self.results.pop().expect("missing result")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_get() {
+        let frames = Frames::default().push('a').push('b');
+        assert_eq!(frames.get(0), Ok(&'b'));
+        assert_eq!(frames.get(1), Ok(&'a'));
+        assert_eq!(frames.get(3), Err(2));
+    }
+
+    #[test]
+    fn test_readback_neutral_values() {
+        // x (λy. y) at depth 1, where x is the first free variable
+        let arg = Value::Closure(Symbol::from("y"));
+        let Value::Neutral(x) = Value::free(0) else { unreachable!() };
+        let value = Value::apply_neutral(x, arg);
+        let mut readback = Readback::new();
+        readback.push(value, 1);
+        readback.run(|readback, value, depth| {
+            if let Some(hint) = readback.value(value, depth, |arg| arg) {
+                readback.push_abs(hint, Value::Level(depth), depth);
+            }
+        });
+        let expected = DbTerm::App(
+            Box::new(DbTerm::Var(1)),
+            Box::new(DbTerm::Abs(Symbol::from("y"), Box::new(DbTerm::Var(0)))),
+        );
+        assert_eq!(readback.finish(), expected);
+    }
+}
@@ -1,36 +1,342 @@
+use std::fs;
 use std::io;
-use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use lc::dot::*;
+use lc::env::*;
 use lc::eval::*;
+use lc::graph::*;
 use lc::parser::*;
+use lc::repl::*;
+
+const USAGE: &str = "\
+Usage:
+  lc [options]                 start the interactive interpreter
+  lc run [options] <file.lc>   evaluate the statements in a file
+  lc eval [options] -e <term>  evaluate a single term
+  lc dot [options] <file.lc>   print a Graphviz diagram for every term in a file:
+                               its syntax tree, or with --trace its reduction
+                               sequence, or with --graph its reduction graph
+
+Options:
+  -s, --strategy <name>  reduction strategy: normal, applicative, cbn, cbv or head
+  -b, --backend <name>   evaluator: substitution, krivine, cek or secd
+  --fuel <steps>         maximum number of reduction steps per term
+  --depth <steps>        maximum number of reduction steps drawn by dot --trace
+                         and --graph (default 10)
+  --eta                  η-reduce results, giving βη-normal forms";
+
+/// Exit code for a source that could not be parsed.
+const EXIT_PARSE_ERROR: u8 = 1;
+/// Exit code for a term whose evaluation was aborted.
+const EXIT_EVAL_ERROR: u8 = 2;
+/// Exit code for invalid command line arguments.
+const EXIT_USAGE: u8 = 64;
+/// Exit code for a file that could not be read.
+const EXIT_NO_INPUT: u8 = 66;
+
+/// Number of reduction steps drawn by `lc dot` without `--depth`, small
+/// enough that diagrams of divergent terms stay readable.
+const DOT_DEPTH: usize = 10;
+
+/// What the interpreter was asked to do on the command line.
+enum Command {
+    Help,
+    Repl,
+    Run(String),
+    Eval(String),
+    Dot(String, Diagram),
+}
+
+/// What `lc dot` draws for each term.
+#[derive(Clone, Copy)]
+enum Diagram {
+    Tree,
+    Trace,
+    Graph,
+}
+
+/// Settings shared by all commands.
+struct Options {
+    strategy: Strategy,
+    backend: Backend,
+    limits: Limits,
+    /// Maximum number of reduction steps drawn by `lc dot`.
+    depth: usize,
+    eta: bool,
+}
 
 /// Driver code to run the lambda calculus evaluator.
-// NOTE!! the parser I copied is a bit shitty, so all function
-// applications must be surrounded by parentheses.
-//
-// EXAMPLE: ((λx. x) (λy. y)) instead of (λx. x) (λy. y)
-fn main() {
-    loop {
-        let mut input = String::new();
-        print!("Introduce a lambda term: ");
-        io::stdout().flush().expect("Could not flush buffer");
-        
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                match parse(&input.trim()) {
-                    Ok(t) => {
-                        println!("Original term: {}", t);
-                        let result = eval(&t);
-                        println!("Evaluated term: {result}")
-                    },
-                    Err(error) => {
-                        println!("Parse error: {error}")
-                    },
+///
+/// Applications are written by juxtaposition, e.g. `(λx. x) (λy. y) z`,
+/// and `λf x. f (f x)` abbreviates `λf. λx. f (f x)`.
+/// `def name = term` binds a name for the following inputs.
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, options) = match parse_args(&args) {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            eprintln!("error: {message}\n\n{USAGE}");
+            return ExitCode::from(EXIT_USAGE);
+        }
+    };
+
+    match command {
+        Command::Help => {
+            println!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Command::Repl => {
+            repl(&options);
+            ExitCode::SUCCESS
+        }
+        Command::Run(path) => match fs::read_to_string(&path) {
+            Ok(source) => run(&source, Some(&path), &options),
+            Err(error) => {
+                eprintln!("error: cannot read {path}: {error}");
+                ExitCode::from(EXIT_NO_INPUT)
+            }
+        },
+        Command::Eval(source) => run(&source, None, &options),
+        Command::Dot(path, diagram) => match fs::read_to_string(&path) {
+            Ok(source) => dot(&source, &path, diagram, &options),
+            Err(error) => {
+                eprintln!("error: cannot read {path}: {error}");
+                ExitCode::from(EXIT_NO_INPUT)
+            }
+        },
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<(Command, Options), String> {
+    let mut options = Options {
+        strategy: Strategy::default(),
+        backend: Backend::default(),
+        limits: Limits::default(),
+        depth: DOT_DEPTH,
+        eta: false,
+    };
+    let mut positional = Vec::new();
+    let mut expression = None;
+    let mut diagram = Diagram::Tree;
+    // the first option given that only `lc dot` accepts
+    let mut dot_option = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "-s" | "--strategy" => options.strategy = value()?.parse()?,
+            "-b" | "--backend" => options.backend = value()?.parse()?,
+            "--fuel" => {
+                let fuel = value()?;
+                let steps = fuel.parse().map_err(|_| format!("invalid fuel: {fuel}"))?;
+                options.limits = Limits::steps(steps);
+            }
+            "--depth" => {
+                let depth = value()?;
+                options.depth = depth.parse().map_err(|_| format!("invalid depth: {depth}"))?;
+                dot_option.get_or_insert(arg);
+            }
+            "--eta" => options.eta = true,
+            "--trace" => {
+                diagram = Diagram::Trace;
+                dot_option.get_or_insert(arg);
+            }
+            "--graph" => {
+                diagram = Diagram::Graph;
+                dot_option.get_or_insert(arg);
+            }
+            "-e" | "--expression" => expression = Some(value()?.clone()),
+            "-h" | "--help" => return Ok((Command::Help, options)),
+            _ if arg.starts_with('-') && arg.len() > 1 => return Err(format!("unknown option: {arg}")),
+            _ => positional.push(arg.as_str()),
+        }
+    }
+
+    if !options.backend.supports(options.strategy) {
+        return Err(format!(
+            "the {} back end does not support the {} strategy",
+            options.backend, options.strategy
+        ));
+    }
+
+    if let Some(option) = dot_option.filter(|_| positional.first() != Some(&"dot")) {
+        return Err(format!("{option} can only be used with dot"));
+    }
+
+    let command = match (positional.as_slice(), expression) {
+        ([], None) => Command::Repl,
+        (["run", path], None) => Command::Run(path.to_string()),
+        (["eval"], Some(expression)) => Command::Eval(expression),
+        (["dot", path], None) => Command::Dot(path.to_string(), diagram),
+        (["eval"], None) => return Err("eval requires a term, e.g. lc eval -e 'λx. x'".to_string()),
+        ([], Some(_)) => return Err("-e can only be used with eval".to_string()),
+        _ => return Err(format!("unexpected arguments: {}", positional.join(" "))),
+    };
+    Ok((command, options))
+}
+
+/// Evaluates all statements of a program and prints the result of every term.
+fn run(source: &str, file_name: Option<&str>, options: &Options) -> ExitCode {
+    let statements = match parse_program(source) {
+        Ok(statements) => statements,
+        Err(error) => {
+            let rendered = match file_name {
+                Some(name) => error.render_file(source, name),
+                None => error.render(source),
+            };
+            eprintln!("{rendered}");
+            return ExitCode::from(EXIT_PARSE_ERROR);
+        }
+    };
+
+    let mut env = Env::new();
+    for statement in statements {
+        match statement {
+            Statement::Def(name, t) => env.define(name, &t),
+            Statement::Term(t) => match eval_on(&env.resolve(&t), options.strategy, options.limits, options.backend) {
+                Ok(result) if options.eta => println!("{}", eta_reduce(&result)),
+                Ok(result) => println!("{result}"),
+                Err(error) => {
+                    eprintln!("error: {error}");
+                    return ExitCode::from(EXIT_EVAL_ERROR);
                 }
             },
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Prints a DOT diagram for every term of a program, after resolving definitions.
+fn dot(source: &str, file_name: &str, diagram: Diagram, options: &Options) -> ExitCode {
+    let statements = match parse_program(source) {
+        Ok(statements) => statements,
+        Err(error) => {
+            eprintln!("{}", error.render_file(source, file_name));
+            return ExitCode::from(EXIT_PARSE_ERROR);
+        }
+    };
+
+    let limits = GraphLimits {
+        max_depth: options.depth,
+        ..GraphLimits::default()
+    };
+    let mut env = Env::new();
+    for statement in statements {
+        match statement {
+            Statement::Def(name, t) => env.define(name, &t),
+            Statement::Term(t) => {
+                let t = env.resolve(&t);
+                let output = match diagram {
+                    Diagram::Tree => to_dot(&t),
+                    Diagram::Trace => trace_to_dot(&t, options.strategy, options.depth),
+                    Diagram::Graph => graph_to_dot(&reduction_graph(&t, limits)),
+                };
+                print!("{output}");
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Line editor support for the REPL: completes commands and defined names.
+struct LineHelper {
+    names: Vec<String>,
+}
+
+impl Completer for LineHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.names))
+    }
+}
+
+impl Hinter for LineHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LineHelper {}
+
+impl Validator for LineHelper {}
+
+impl Helper for LineHelper {}
+
+/// The file the REPL history is kept in, `~/.lc_history`.
+fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(PathBuf::from(home).join(".lc_history"))
+}
+
+fn repl(options: &Options) {
+    let mut editor = match Editor::<LineHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("error: cannot start line editor: {error}");
+            return;
+        }
+    };
+    editor.set_helper(Some(LineHelper { names: Vec::new() }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // there is no history yet on the first start
+        let _ = editor.load_history(path);
+    }
+
+    let mut repl = Repl::new(options.strategy, options.backend, options.limits, options.eta);
+    let mut stdout = io::stdout();
+    println!("Type :help for a list of commands.");
+    let mut input = String::new();
+    loop {
+        let prompt = if input.is_empty() { "λ> " } else { ".. " };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+                // keep reading while the statement is unfinished
+                if is_incomplete(&input) {
+                    continue;
+                }
+            }
+            Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
             Err(error) => {
-                println!("error: {error}")
-            },
+                eprintln!("error: {error}");
+                break;
+            }
+        }
+
+        let statement = std::mem::take(&mut input);
+        if !statement.trim().is_empty() {
+            // keep the line breaks, which end comments and delimit layout blocks
+            let _ = editor.add_history_entry(statement.trim_end());
+        }
+        match repl.handle(&statement, &mut stdout) {
+            Ok(Control::Continue) => {}
+            Ok(Control::Quit) => break,
+            Err(error) => eprintln!("error: {error}"),
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.names = repl.names();
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(error) = editor.save_history(path) {
+            eprintln!("error: cannot save history to {}: {error}", path.display());
         }
     }
 }
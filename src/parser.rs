@@ -1,26 +1,131 @@
 use crate::term::*;
-use std::{fmt, iter::Peekable, str::Chars};
+use std::{fmt, iter::Peekable, str::CharIndices};
 
 // Source: https://github.com/notJoon/lambda
 // Author: Lee ByeongJun
 
-#[derive(Debug, PartialEq)]
-pub enum ParseError {
+/// An error reported by [`parse`], with the position in the source where it occurred.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Byte offset into the source.
+    pub offset: usize,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column in characters, starting at 1.
+    pub column: usize,
+    /// What the parser would have accepted at this position.
+    pub expected: Vec<Expected>,
+}
+
+/// The kind of a [`ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
     UnexpectedCharacter(char),
+    UnexpectedKeyword(&'static str),
+    UnexpectedEnd,
     UnmatchedParenthesis,
-    InvalidLambda,
-    InvalidApplication,
-    InvalidVariable,
 }
 
-impl fmt::Display for ParseError {
+/// Something the parser expected to find at the position of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// A variable name.
+    Variable,
+    /// The start of a term: a variable, `(` or a lambda.
+    Term,
+    /// A specific token such as `)` or `.`.
+    Token(&'static str),
+    /// The end of the input.
+    End,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedCharacter(c) => write!(f, "Unexpected character: {}", c),
+            ParseErrorKind::UnexpectedKeyword(k) => write!(f, "Unexpected keyword: {}", k),
+            ParseErrorKind::UnexpectedEnd => write!(f, "Unexpected end of input"),
+            ParseErrorKind::UnmatchedParenthesis => write!(f, "Unmatched parenthesis"),
+        }
+    }
+}
+
+impl fmt::Display for Expected {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParseError::InvalidLambda => write!(f, "Invalid lambda expression"),
-            ParseError::InvalidApplication => write!(f, "Invalid application expression"),
-            ParseError::InvalidVariable => write!(f, "Invalid variable expression"),
-            ParseError::UnexpectedCharacter(c) => write!(f, "Unexpected character: {}", c),
-            ParseError::UnmatchedParenthesis => write!(f, "Unmatched parenthesis"),
+            Expected::Variable => write!(f, "a variable"),
+            Expected::Term => write!(f, "a term"),
+            Expected::Token(token) => write!(f, "`{}`", token),
+            Expected::End => write!(f, "end of input"),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)?;
+        if !self.expected.is_empty() {
+            write!(f, ", expected {}", self.expected_list())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// Renders the error with the offending line of `source` and a caret
+    /// pointing at the error position, e.g.
+    /// ```text
+    /// error: Unexpected character: .
+    ///  --> 1:3
+    ///   |
+    /// 1 | x . y
+    ///   |   ^ expected end of input or a term
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        self.render_location(source, None)
+    }
+
+    /// Like [`ParseError::render`], but names the file the source was read from.
+    pub fn render_file(&self, source: &str, file_name: &str) -> String {
+        self.render_location(source, Some(file_name))
+    }
+
+    fn render_location(&self, source: &str, file_name: Option<&str>) -> String {
+        let line = source.lines().nth(self.line - 1).unwrap_or("");
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        // keep tabs so that the caret lines up with the source line
+        let indent: String = line
+            .chars()
+            .take(self.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let mut out = format!("error: {}\n", self.kind);
+        let file_name = file_name.map(|name| format!("{}:", name)).unwrap_or_default();
+        out.push_str(&format!(
+            "{}--> {}{}:{}\n",
+            gutter, file_name, self.line, self.column
+        ));
+        out.push_str(&format!("{} |\n", gutter));
+        out.push_str(&format!("{} | {}\n", number, line));
+        out.push_str(&format!("{} | {}^", gutter, indent));
+        if !self.expected.is_empty() {
+            out.push_str(&format!(" expected {}", self.expected_list()));
+        }
+        out
+    }
+
+    /// `a`, `a or b`, `a, b or c`
+    fn expected_list(&self) -> String {
+        let items: Vec<String> = self.expected.iter().map(|e| e.to_string()).collect();
+        match items.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+            None => String::new(),
         }
     }
 }
@@ -28,7 +133,32 @@ impl fmt::Display for ParseError {
 type TermResult = Result<Term, ParseError>;
 
 struct Parser<'a> {
-    chars: Peekable<Chars<'a>>,
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    /// Alternatives that were tried and rejected at `expected_offset`,
+    /// reported if parsing fails at that position.
+    expected: Vec<Expected>,
+    expected_offset: usize,
+    /// Whether a line starting at column 0 outside of parentheses begins a new statement.
+    layout: bool,
+    /// Number of currently open parentheses.
+    depth: usize,
+}
+
+/// Words that cannot be used as variable names, unless escaped as `#in`.
+pub const KEYWORDS: [&str; 4] = ["fun", "let", "in", "def"];
+
+fn is_variable_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// A top-level statement, as entered in the REPL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// `def name = term` binds `name` for all following statements.
+    Def(Symbol, Term),
+    /// A term to evaluate.
+    Term(Term),
 }
 
 /// A parser for lambda calculus expressions.
@@ -36,138 +166,668 @@ impl<'a> Parser<'a> {
     /// Create a new parser for the given input.
     fn new(input: &'a str) -> Self {
         Self {
-            chars: input.chars().peekable(),
+            input,
+            chars: input.char_indices().peekable(),
+            expected: Vec::new(),
+            expected_offset: 0,
+            layout: false,
+            depth: 0,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+
+    /// Byte offset of the next character.
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |&(i, _)| i)
+    }
+
+    /// Record that `expected` would have been accepted at the current position.
+    fn expect(&mut self, expected: &[Expected]) {
+        let offset = self.offset();
+        if offset != self.expected_offset {
+            self.expected.clear();
+            self.expected_offset = offset;
+        }
+        for e in expected {
+            if !self.expected.contains(e) {
+                self.expected.push(*e);
+            }
+        }
+    }
+
+    /// Error about the next character, which is none of the `expected` alternatives.
+    fn error(&mut self, expected: &[Expected]) -> ParseError {
+        self.expect(expected);
+        let kind = match (self.peek_keyword(), self.peek()) {
+            (Some(keyword), _) => ParseErrorKind::UnexpectedKeyword(keyword),
+            (None, Some(')')) => ParseErrorKind::UnmatchedParenthesis,
+            (None, Some(c)) => ParseErrorKind::UnexpectedCharacter(c),
+            (None, None) => ParseErrorKind::UnexpectedEnd,
+        };
+        let offset = self.offset();
+        let before = &self.input[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        ParseError {
+            kind,
+            offset,
+            line,
+            column,
+            expected: self.expected.clone(),
+        }
+    }
+
+    /// Parse a definition `def binding` or a term.
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        self.skip_whitespace();
+        if self.eat_keyword("def") {
+            let (name, params) = self.parse_binding()?;
+            let value = self.parse_term()?;
+            Ok(Statement::Def(name, abstract_over(params, value)))
+        } else {
+            Ok(Statement::Term(self.parse_term()?))
+        }
+    }
+
+    /// Returns `result` if the whole input has been consumed.
+    fn finish<T>(&mut self, result: T) -> Result<T, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(result),
+            Some(_) => Err(self.error(&[Expected::End])),
         }
     }
 
+    /// Skips whitespace and comments, which run from `--` to the end of the line.
     fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.chars.peek() {
+        while let Some(c) = self.peek() {
             if c.is_whitespace() {
-                self.chars.next();
+                self.next();
+            } else if self.eat("--") {
+                while self.next().is_some_and(|c| c != '\n') {}
             } else {
                 break;
             }
         }
     }
 
-    /// Parse a non-application term
-    fn parse_lambda(&mut self) -> TermResult {
-        if self.chars.next() == Some('λ') {
-            let bind = self.parse_var().map_err(|_| ParseError::InvalidLambda)?;
+    /// Whether the current position starts a new statement by the layout rule.
+    fn at_new_statement(&mut self) -> bool {
+        let offset = self.offset();
+        self.layout && self.depth == 0 && offset > 0 && self.input[..offset].ends_with('\n')
+    }
 
-            self.skip_whitespace();
+    /// Consume `token` if the input continues with it.
+    fn eat(&mut self, token: &str) -> bool {
+        let mut lookahead = self.chars.clone();
+        if token.chars().all(|c| lookahead.next().map(|(_, d)| d) == Some(c)) {
+            self.chars = lookahead;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The variable name or keyword at the current position, without consuming it.
+    fn peek_word(&self) -> String {
+        self.chars
+            .clone()
+            .map(|(_, c)| c)
+            .take_while(|&c| is_variable_char(c))
+            .collect()
+    }
+
+    /// The keyword at the current position, if any.
+    fn peek_keyword(&self) -> Option<&'static str> {
+        let word = self.peek_word();
+        KEYWORDS.into_iter().find(|keyword| *keyword == word)
+    }
+
+    /// Consume the keyword `keyword` if it is at the current position.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        self.peek_keyword() == Some(keyword) && self.eat(keyword)
+    }
+
+    /// Whether a variable, possibly an escaped keyword, starts at the current position.
+    fn at_variable(&mut self) -> bool {
+        match self.peek() {
+            Some('#') => true,
+            Some(c) => is_variable_char(c) && self.peek_keyword().is_none(),
+            None => false,
+        }
+    }
 
-            if self.chars.next() == Some('.') {
-                let body = self.parse_term()?;
-                Ok(Term::Abs(
-                    bind,
-                    Box::new(body),
-                ))
+    /// Whether a term that extends as far right as possible, i.e. a lambda
+    /// abstraction or a `let`, starts at the current position.
+    fn at_open_term(&mut self) -> bool {
+        match self.peek() {
+            Some('λ') | Some('\\') => true,
+            Some(_) => matches!(self.peek_keyword(), Some("fun") | Some("let")),
+            None => false,
+        }
+    }
+
+    /// Parse a term, i.e. a lambda abstraction, a `let` or an application.
+    ///
+    /// Terms nested in parentheses, abstractions and `let`s are kept on an
+    /// explicit stack of frames instead of the native stack, so that deeply
+    /// nested input is parsed without overflowing it.
+    fn parse_term(&mut self) -> TermResult {
+        let mut frames = Vec::new();
+        // the application parsed so far in the innermost term
+        let mut app: Option<Term> = None;
+        loop {
+            self.skip_whitespace();
+            if app.is_some() && self.at_new_statement() {
+                self.expect(&[Expected::Term]);
+            } else if self.at_open_term() {
+                // an abstraction or a `let` extends as far right as possible,
+                // so it is the last argument of the application
+                if let Some(function) = app.take() {
+                    frames.push(Frame::Arg(function));
+                }
+                frames.push(self.parse_open_term()?);
+                continue;
             } else {
-                Err(ParseError::InvalidLambda)
+                match self.peek() {
+                    Some('(') => {
+                        // consume the '('
+                        self.next();
+                        self.depth += 1;
+                        frames.push(Frame::Paren(app.take()));
+                        continue;
+                    }
+                    Some(_) if self.at_variable() => {
+                        let var = Term::Var(self.parse_var()?);
+                        app = Some(apply(app, var));
+                        continue;
+                    }
+                    // another argument would have been fine here
+                    _ if app.is_some() => self.expect(&[Expected::Term]),
+                    _ => return Err(self.error(&[Expected::Term])),
+                }
+            }
+
+            // the innermost term is complete, plug it into the frames
+            let mut term = app.take().expect("checked above");
+            loop {
+                match frames.pop() {
+                    None => return Ok(term),
+                    Some(Frame::Arg(function)) => term = Term::App(Box::new(function), Box::new(term)),
+                    Some(Frame::Lambda(binders)) => term = abstract_over(binders, term),
+                    Some(Frame::LetValue(name, params)) => {
+                        self.skip_whitespace();
+                        if !self.eat_keyword("in") {
+                            return Err(self.error(&[Expected::Token("in")]));
+                        }
+                        // like the body of an abstraction, the body of a let
+                        // extends as far right as possible
+                        frames.push(Frame::LetBody(name, abstract_over(params, term)));
+                        break;
+                    }
+                    Some(Frame::LetBody(name, value)) => {
+                        term = Term::App(Box::new(Term::Abs(name, Box::new(term))), Box::new(value));
+                    }
+                    Some(Frame::Paren(function)) => {
+                        self.skip_whitespace();
+                        if self.peek() != Some(')') {
+                            return Err(self.error(&[Expected::Token(")")]));
+                        }
+                        self.next();
+                        self.depth -= 1;
+                        app = Some(apply(function, term));
+                        break;
+                    }
+                }
             }
-        } else {
-            Err(ParseError::UnexpectedCharacter('λ'))
         }
     }
 
-    /// Parse an application
-    fn parse_application(&mut self) -> TermResult {
-        let mut terms: Vec<Term> = vec![self.parse_term()?];
-
-        while let Ok(term) = self.parse_term() {
-            terms.push(term);
+    /// Parse the start of a lambda abstraction or a `let`, up to where its
+    /// body or value begins.
+    fn parse_open_term(&mut self) -> Result<Frame, ParseError> {
+        if self.eat_keyword("let") {
+            let (name, params) = self.parse_binding()?;
+            Ok(Frame::LetValue(name, params))
+        } else {
+            Ok(Frame::Lambda(self.parse_lambda()?))
         }
+    }
 
-        if terms.is_empty() {
-            Err(ParseError::InvalidApplication)
-        } else if terms.len() == 1 {
-            Ok(terms.pop().unwrap())
+    /// Parse the binders and the separator of a lambda abstraction with one
+    /// or more binders, written as `λx. M`, `\x. M`, `\x -> M` or `fun x => M`.
+    /// `λx y. M` is shorthand for `λx. λy. M`.
+    fn parse_lambda(&mut self) -> Result<Vec<Symbol>, ParseError> {
+        let separators: &[&'static str] = if self.eat_keyword("fun") {
+            &["=>"]
         } else {
-            let mut iter = terms.into_iter();
-            let mut app = iter.next().unwrap();
-
-            for term in iter {
-                app = Term::App(
-                    Box::new(app),
-                    Box::new(term),
-                );
+            match self.peek() {
+                Some('λ') | Some('\\') => {
+                    self.next();
+                    &[".", "->"]
+                }
+                _ => return Err(self.error(&[Expected::Token("λ")])),
             }
+        };
+
+        let binders = self.parse_binders()?;
+        if binders.is_empty() {
+            return Err(self.error(&[Expected::Variable]));
+        }
+        if !separators.iter().any(|separator| self.eat(separator)) {
+            let expected: Vec<_> = separators.iter().map(|s| Expected::Token(s)).collect();
+            return Err(self.error(&expected));
+        }
+        Ok(binders)
+    }
 
-            Ok(app)
+    /// Parse `name params* =` of a binding `name params* = term` as used by
+    /// `let` and `def`, which is shorthand for `name = λparams. term`.
+    fn parse_binding(&mut self) -> Result<(Symbol, Vec<Symbol>), ParseError> {
+        self.skip_whitespace();
+        let name = self.parse_var()?;
+        let params = self.parse_binders()?;
+        if !self.eat("=") {
+            return Err(self.error(&[Expected::Token("=")]));
+        }
+        Ok((name, params))
+    }
+
+    /// Parse a possibly empty sequence of variable names separated by whitespace.
+    fn parse_binders(&mut self) -> Result<Vec<Symbol>, ParseError> {
+        let mut binders = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(c) if c == '#' || is_variable_char(c) => binders.push(self.parse_var()?),
+                _ => {
+                    // another binder would have been fine here
+                    self.expect(&[Expected::Variable]);
+                    return Ok(binders);
+                }
+            }
         }
     }
 
     /// Parse a variable
-    fn parse_var(&mut self) -> Result<String, ParseError> {
+    fn parse_var(&mut self) -> Result<Symbol, ParseError> {
+        // `#in` is the variable `in`, which is otherwise a keyword
+        if self.peek() == Some('#') {
+            self.next();
+        } else if self.peek_keyword().is_some() {
+            return Err(self.error(&[Expected::Variable]));
+        }
+
         let mut name = String::new();
 
-        while let Some(c) = self.chars.peek() {
-            if c.is_alphanumeric() || *c == '_' {
-                name.push(*c);
-                self.chars.next();
+        while let Some(c) = self.peek() {
+            if is_variable_char(c) {
+                name.push(c);
+                self.next();
             } else {
                 break;
             }
         }
 
         if name.is_empty() {
-            Err(ParseError::InvalidVariable)
+            Err(self.error(&[Expected::Variable]))
         } else {
-            Ok(name)
+            Ok(Symbol::intern(&name))
         }
     }
+}
 
-    /// Parse a non-application term (i.e., a lambda abstraction or a variable) from the input.
-    fn parse_term(&mut self) -> TermResult {
-        self.skip_whitespace();
+/// A term that the parser is in the middle of, waiting for the term that
+/// is parsed next.
+enum Frame {
+    /// An application whose last argument is an abstraction or a `let`.
+    Arg(Term),
+    /// Abstractions over the binders, waiting for their body.
+    Lambda(Vec<Symbol>),
+    /// `let name params* =`, waiting for the value.
+    LetValue(Symbol, Vec<Symbol>),
+    /// `let name = value in`, waiting for the body.
+    LetBody(Symbol, Term),
+    /// An opening parenthesis after the application to its left, if any.
+    Paren(Option<Term>),
+}
 
-        if self.chars.peek() == Some(&'(') {
-            // consume the '('
-            self.chars.next();
-
-            let term = match self.chars.peek() {
-                Some('λ') => self.parse_lambda()?,
-                Some(_) => self.parse_application()?,
-                None => return Err(ParseError::UnmatchedParenthesis),
-            };
-            
-            self.chars
-                .next()
-                .and_then(|c| if c == ')' { Some(term) } else { None })
-                .ok_or(ParseError::UnmatchedParenthesis)
-        } else {
-            self.parse_non_application_term()
-        }
+/// Applies `function`, if any, to `arg`.
+fn apply(function: Option<Term>, arg: Term) -> Term {
+    match function {
+        Some(function) => Term::App(Box::new(function), Box::new(arg)),
+        None => arg,
     }
+}
 
-    /// Parse a non-application term (i.e., a lambda abstraction or a variable) from the input.
-    ///
-    /// This function is used to parse the sub-expressions of an application. Since an application
-    /// consists of a sequence of non-application terms, this function ensures that only lambda
-    /// abstractions or variables are parsed within an application.
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(JsonTerm)` - A successfully parsed non-application term (lambda abstraction or variable).
-    /// * `Err(ParseError::InvalidApplication)` - If the input doesn't match a valid non-application term.
-    fn parse_non_application_term(&mut self) -> TermResult {
-        self.skip_whitespace();
-
-        match self.chars.peek() {
-            Some(&'λ') => self.parse_lambda(),
-            Some(c) if c.is_alphanumeric() || *c == '_' => Ok(Term::Var(self.parse_var()?)),
-            _ => Err(ParseError::InvalidApplication),
-        }
-    }
+/// Wraps `body` in abstractions over `binders`, the first binder being outermost.
+fn abstract_over(binders: Vec<Symbol>, body: Term) -> Term {
+    binders
+        .into_iter()
+        .rev()
+        .fold(body, |body, bind| Term::Abs(bind, Box::new(body)))
 }
 
+/// Parses a lambda term.
+///
+/// Grammar:
+/// ```text
+/// term        ::= open | application
+/// application ::= atom+ open?
+/// atom        ::= variable | '(' term ')'
+/// open        ::= lambda | let
+/// lambda      ::= ('λ' | '\\') variable+ ('.' | '->') term
+///               | 'fun' variable+ '=>' term
+/// let         ::= 'let' binding 'in' term
+/// binding     ::= variable variable* '=' term
+/// ```
+/// Application is left-associative and the body of a lambda extends as far
+/// right as possible, so `λf x. f (f x) y` means `λf. λx. ((f (f x)) y)`.
+/// `let x = M in N` is shorthand for `(λx. N) M`.
+/// The keywords `fun`, `let`, `in` and `def` cannot be used as variables.
+/// Comments run from `--` to the end of the line.
 pub fn parse(input: &str) -> TermResult {
     let mut parser = Parser::new(input);
     let term = parser.parse_term()?;
-    if parser.chars.peek().is_some() {
-        Err(ParseError::InvalidApplication)
-    } else {
-        Ok(term)
+    parser.finish(term)
+}
+
+/// Parses a statement: either a term or a definition `def binding`,
+/// using the grammar of [`parse`].
+pub fn parse_statement(input: &str) -> Result<Statement, ParseError> {
+    let mut parser = Parser::new(input);
+    let statement = parser.parse_statement()?;
+    parser.finish(statement)
+}
+
+/// Parses two terms separated by `=`, e.g. `plus one one = two`,
+/// using the grammar of [`parse`].
+pub fn parse_equation(input: &str) -> Result<(Term, Term), ParseError> {
+    let mut parser = Parser::new(input);
+    let left = parser.parse_term()?;
+    parser.skip_whitespace();
+    if !parser.eat("=") {
+        return Err(parser.error(&[Expected::Token("=")]));
+    }
+    let right = parser.parse_term()?;
+    parser.finish((left, right))
+}
+
+/// Parses a program, i.e. a sequence of statements as in a `.lc` file.
+///
+/// Statements are separated by `;` or by starting a new line at column 0
+/// outside of parentheses, so longer statements can be continued on
+/// indented lines:
+/// ```text
+/// -- Church numerals
+/// def plus m n f x =
+///   m f (n f x)
+/// def two = λf x. f (f x)
+/// plus two two
+/// ```
+pub fn parse_program(input: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut parser = Parser::new(input);
+    parser.layout = true;
+    let mut statements = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.peek().is_none() {
+            return Ok(statements);
+        }
+        if parser.eat(";") {
+            continue;
+        }
+        statements.push(parser.parse_statement()?);
+        parser.skip_whitespace();
+        if !(parser.peek().is_none() || parser.peek() == Some(';') || parser.at_new_statement()) {
+            return Err(parser.error(&[Expected::Token(";"), Expected::End]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_application_without_parentheses() {
+        // (λx. x) (λy. y) z
+        let expected = app(app(abs("x", var("x")), abs("y", var("y"))), var("z"));
+        assert_eq!(parse("(λx. x) (λy. y) z"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_application_is_left_associative() {
+        assert_eq!(parse("a b c"), Ok(app(app(var("a"), var("b")), var("c"))));
+        assert_eq!(parse("a (b c)"), Ok(app(var("a"), app(var("b"), var("c")))));
+    }
+
+    #[test]
+    fn test_parse_lambda_extends_to_the_right() {
+        let expected = abs("x", app(app(var("x"), var("y")), var("z")));
+        assert_eq!(parse("λx. x y z"), Ok(expected));
+
+        let expected = app(var("f"), abs("x", app(var("x"), var("y"))));
+        assert_eq!(parse("f λx. x y"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_multiple_binders() {
+        let expected = abs("f", abs("x", app(var("f"), app(var("f"), var("x")))));
+        assert_eq!(parse("λf x. f (f x)"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_fully_parenthesized() {
+        let expected = app(abs("x", var("x")), abs("y", var("y")));
+        assert_eq!(parse("((λx. x) (λy. y))"), Ok(expected));
+    }
+
+    fn parse_error(input: &str) -> ParseError {
+        parse(input).expect_err("input should be rejected")
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_error("").kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(parse_error("(x y").kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(parse_error("x y)").kind, ParseErrorKind::UnmatchedParenthesis);
+        assert_eq!(parse_error("x (y").kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(parse_error("λ. x").kind, ParseErrorKind::UnexpectedCharacter('.'));
+        assert_eq!(parse_error("λx x").kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(parse_error("x . y").kind, ParseErrorKind::UnexpectedCharacter('.'));
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let error = parse_error("λx. x\n  (y .\n");
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedCharacter('.'));
+        assert_eq!(error.offset, 12);
+        assert_eq!((error.line, error.column), (2, 6));
+    }
+
+    #[test]
+    fn test_parse_error_expected_tokens() {
+        let error = parse_error("(x y");
+        assert_eq!(error.expected, vec![Expected::Term, Expected::Token(")")]);
+
+        let error = parse_error("λx y z");
+        assert_eq!(
+            error.expected,
+            vec![Expected::Variable, Expected::Token("."), Expected::Token("->")]
+        );
+
+        let error = parse_error("fun x -> x");
+        assert_eq!(error.expected, vec![Expected::Variable, Expected::Token("=>")]);
+    }
+
+    #[test]
+    fn test_parse_error_render() {
+        let source = "λf x.\n  f (x .) y";
+        let error = parse_error(source);
+        let expected = [
+            "error: Unexpected character: .",
+            " --> 2:8",
+            "  |",
+            "2 |   f (x .) y",
+            "  |        ^ expected a term or `)`",
+        ];
+        assert_eq!(error.render(source), expected.join("\n"));
+        assert_eq!(error.to_string(), "2:8: Unexpected character: ., expected a term or `)`");
+    }
+
+    #[test]
+    fn test_parse_ascii_lambda() {
+        let expected = abs("x", app(var("x"), var("y")));
+        assert_eq!(parse("\\x. x y"), Ok(expected.clone()));
+        assert_eq!(parse("\\x -> x y"), Ok(expected.clone()));
+        assert_eq!(parse("fun x => x y"), Ok(expected));
+
+        let expected = abs("x", abs("y", abs("z", var("x"))));
+        assert_eq!(parse("\\x y z. x"), Ok(expected.clone()));
+        assert_eq!(parse("fun x y z => x"), Ok(expected.clone()));
+        assert_eq!(parse("λx y z -> x"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_ascii_lambda_as_argument() {
+        let expected = app(var("f"), abs("x", var("x")));
+        assert_eq!(parse("f \\x. x"), Ok(expected.clone()));
+        assert_eq!(parse("f (fun x => x)"), Ok(expected));
+        // `fun` is a keyword, but may start a variable name
+        assert_eq!(parse("fun funny => funny"), Ok(abs("funny", var("funny"))));
+    }
+
+    #[test]
+    fn test_parse_let() {
+        // let id = λx. x in id y
+        let expected = app(abs("id", app(var("id"), var("y"))), abs("x", var("x")));
+        assert_eq!(parse("let id = λx. x in id y"), Ok(expected));
+
+        // let with parameters and nested lets
+        let expected = app(
+            abs("k", app(abs("a", app(var("k"), var("a"))), var("b"))),
+            abs("x", abs("y", var("x"))),
+        );
+        assert_eq!(parse("let k x y = x in let a = b in k a"), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_keywords_are_not_variables() {
+        assert_eq!(
+            parse_error("λin. x").kind,
+            ParseErrorKind::UnexpectedKeyword("in")
+        );
+        let error = parse_error("let x = y z");
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error.expected, vec![Expected::Term, Expected::Token("in")]);
+        // keywords are whole words only
+        assert_eq!(parse("inx letter"), Ok(app(var("inx"), var("letter"))));
+    }
+
+    #[test]
+    fn test_parse_escaped_keywords() {
+        assert_eq!(parse("λ#in. #in #let"), Ok(abs("in", app(var("in"), var("let")))));
+        assert_eq!(parse("fun #fun => #fun"), Ok(abs("fun", var("fun"))));
+        assert_eq!(parse("#x"), Ok(var("x")));
+        assert_eq!(parse_error("# x").expected, vec![Expected::Variable]);
+    }
+
+    #[test]
+    fn test_parse_equation() {
+        assert_eq!(
+            parse_equation("f x y = λx. x"),
+            Ok((app(app(var("f"), var("x")), var("y")), abs("x", var("x"))))
+        );
+        // the `=` of a `let` belongs to the term
+        assert_eq!(
+            parse_equation("let x = y in x = y"),
+            Ok((app(abs("x", var("x")), var("y")), var("y")))
+        );
+        let error = parse_equation("f x").unwrap_err();
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!(error.expected, vec![Expected::Term, Expected::Token("=")]);
+    }
+
+    #[test]
+    fn test_parse_statement() {
+        assert_eq!(
+            parse_statement("def id = λx. x"),
+            Ok(Statement::Def(Symbol::intern("id"), abs("x", var("x"))))
+        );
+        assert_eq!(
+            parse_statement("def const x y = x"),
+            Ok(Statement::Def(
+                Symbol::intern("const"),
+                abs("x", abs("y", var("x")))
+            ))
+        );
+        assert_eq!(
+            parse_statement("id z"),
+            Ok(Statement::Term(app(var("id"), var("z"))))
+        );
+        assert!(parse("def id = λx. x").is_err());
+    }
+
+    #[test]
+    fn test_parse_deep_term_is_stack_safe() {
+        // f (f (… (f x)))
+        let depth = 100_000;
+        let source = format!("{}x{}", "f (".repeat(depth), ")".repeat(depth));
+        let mut expected = var("x");
+        for _ in 0..depth {
+            expected = app(var("f"), expected);
+        }
+        assert_eq!(parse(&source), Ok(expected));
+
+        // λx. x λx. x … λx. x x, which is how such terms are printed
+        let mut term = var("x");
+        for _ in 0..depth {
+            term = abs("x", app(var("x"), term));
+        }
+        assert_eq!(parse(&crate::pretty::pretty_print(&term)), Ok(term));
+
+        let source = format!("{}x", "let x = y in ".repeat(depth));
+        assert!(parse(&source).is_ok());
+    }
+
+    #[test]
+    fn test_parse_program() {
+        let source = "-- identity\ndef id = λx.\n  x -- the body\n\nid\n  (id y)\nz; id";
+        let expected = vec![
+            Statement::Def(Symbol::intern("id"), abs("x", var("x"))),
+            Statement::Term(app(var("id"), app(var("id"), var("y")))),
+            Statement::Term(var("z")),
+            Statement::Term(var("id")),
+        ];
+        assert_eq!(parse_program(source), Ok(expected));
+    }
+
+    #[test]
+    fn test_parse_program_continues_inside_parentheses() {
+        let source = "f (a\nb)\ng";
+        let expected = vec![
+            Statement::Term(app(var("f"), app(var("a"), var("b")))),
+            Statement::Term(var("g")),
+        ];
+        assert_eq!(parse_program(source), Ok(expected));
+        assert_eq!(parse_program("  \n-- nothing\n"), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_parse_program_error_location() {
+        let source = "def id = λx. x\nid (y\n";
+        let error = parse_program(source).expect_err("input should be rejected");
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedEnd);
+        assert_eq!((error.line, error.column), (3, 1));
+        assert!(error.render_file(source, "id.lc").contains(" --> id.lc:3:1"));
     }
 }
@@ -1,15 +1,214 @@
+use crate::parser::KEYWORDS;
 use crate::term::*;
 use std::fmt;
 
-/// Pretty prints a term.
+/// Pretty prints a term using the usual conventions of the lambda calculus:
+/// - Application is left-associative: `x y z` means `(x y) z`.
+/// - The body of an abstraction extends as far right as possible:
+///   `λx. x y` means `λx. (x y)`.
+///
+/// Parentheses are only emitted where these conventions would otherwise
+/// change the meaning of the term, and names that are keywords are escaped
+/// as in `λ#in. #in`, so that parsing the output yields the original term again.
+///
+/// Examples:
+///   `abs("x", abs("y", app(var("x"), var("y"))))` prints as `λx. λy. x y`.
+///   `app(abs("x", var("x")), var("y"))` prints as `(λx. x) y`.
+///   `app(var("x"), app(var("y"), var("z")))` prints as `x (y z)`.
 pub fn pretty_print(term: &Term) -> String {
-    format!("{:?}", term)
-    // TODO: Implement pretty printing for lambda calculus terms.
+    pretty_print_with(term, Notation::Unicode)
+}
+
+/// The way lambda abstractions are written by [`pretty_print_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// `λx. M`
+    #[default]
+    Unicode,
+    /// `\x. M`, which is easier to type.
+    Ascii,
+}
+
+/// Pretty prints a term like [`pretty_print`], writing abstractions in the given `notation`.
+pub fn pretty_print_with(term: &Term, notation: Notation) -> String {
+    let lambda = match notation {
+        Notation::Unicode => "λ",
+        Notation::Ascii => "\\",
+    };
+
+    enum Item<'a> {
+        Term(&'a Term, bool),
+        Text(&'static str),
+    }
+
+    // The second component of `Item::Term` is set when something follows the
+    // term without being enclosed by parentheses (e.g. the argument of an
+    // application), in which case an abstraction at the right end of the term
+    // must be parenthesized, otherwise its body would swallow what follows.
+    let mut out = String::new();
+    let mut stack = vec![Item::Term(term, false)];
+    while let Some(item) = stack.pop() {
+        match item {
+            Item::Text(text) => out.push_str(text),
+            Item::Term(Term::Var(x), _) => push_name(&mut out, *x),
+            Item::Term(Term::Abs(param, body), guard_lambda) => {
+                if guard_lambda {
+                    out.push('(');
+                    stack.push(Item::Text(")"));
+                }
+                out.push_str(lambda);
+                push_name(&mut out, *param);
+                out.push_str(". ");
+                stack.push(Item::Term(body, false));
+            }
+            Item::Term(Term::App(t1, t2), guard_lambda) => {
+                // Items are pushed in reverse order.
+                // Application is left-associative, so a nested application on the
+                // right side needs parentheses.
+                match **t2 {
+                    Term::App(_, _) => {
+                        stack.push(Item::Text(")"));
+                        stack.push(Item::Term(t2, false));
+                        stack.push(Item::Text("("));
+                    }
+                    _ => stack.push(Item::Term(t2, guard_lambda)),
+                }
+                stack.push(Item::Text(" "));
+                // The left side of an application is always followed by the argument.
+                match **t1 {
+                    Term::Abs(_, _) => {
+                        stack.push(Item::Text(")"));
+                        stack.push(Item::Term(t1, false));
+                        stack.push(Item::Text("("));
+                    }
+                    _ => stack.push(Item::Term(t1, true)),
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Appends a variable name, escaping it if it is a keyword.
+fn push_name(out: &mut String, name: Symbol) {
+    if KEYWORDS.contains(&name.as_str()) {
+        out.push('#');
+    }
+    out.push_str(name.as_str());
 }
 
 /// Display trait implementation for Term.
+/// The alternate flag (`{:#}`) selects the ASCII notation.
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", pretty_print(self))
+        let notation = if f.alternate() {
+            Notation::Ascii
+        } else {
+            Notation::Unicode
+        };
+        write!(f, "{}", pretty_print_with(self, notation))
+    }
+}
+
+/// Debug trait implementation for Term, e.g. `Term(λx. x y)`.
+/// Not derived, since a derived implementation would recurse.
+impl fmt::Debug for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Term({})", pretty_print(self))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_pretty_print_variable() {
+        assert_eq!(pretty_print(&var("x")), "x");
+    }
+
+    #[test]
+    fn test_pretty_print_nested_abstraction() {
+        let term = abs("x", abs("y", app(var("x"), var("y"))));
+        assert_eq!(pretty_print(&term), "λx. λy. x y");
+    }
+
+    #[test]
+    fn test_pretty_print_application_is_left_associative() {
+        let term = app(app(var("x"), var("y")), var("z"));
+        assert_eq!(pretty_print(&term), "x y z");
+
+        let term = app(var("x"), app(var("y"), var("z")));
+        assert_eq!(pretty_print(&term), "x (y z)");
+    }
+
+    #[test]
+    fn test_pretty_print_abstraction_in_application() {
+        let term = app(abs("x", var("x")), var("y"));
+        assert_eq!(pretty_print(&term), "(λx. x) y");
+
+        let term = app(app(var("f"), abs("x", var("x"))), var("y"));
+        assert_eq!(pretty_print(&term), "f (λx. x) y");
+
+        // a trailing abstraction extends to the end anyway
+        let term = app(var("f"), abs("x", app(var("x"), var("y"))));
+        assert_eq!(pretty_print(&term), "f λx. x y");
+    }
+
+    #[test]
+    fn test_pretty_print_keywords() {
+        let term = abs("in", app(var("in"), var("fun")));
+        assert_eq!(pretty_print(&term), "λ#in. #in #fun");
+    }
+
+    #[test]
+    fn test_pretty_print_display() {
+        let term = abs("f", abs("x", app(var("f"), app(var("f"), var("x")))));
+        assert_eq!(format!("{term}"), "λf. λx. f (f x)");
+        assert_eq!(format!("{term:?}"), "Term(λf. λx. f (f x))");
+    }
+
+    #[test]
+    fn test_pretty_print_round_trip() {
+        let terms = vec![
+            var("x"),
+            abs("x", var("x")),
+            app(abs("x", var("x")), abs("y", var("y"))),
+            app(app(var("f"), abs("x", var("x"))), var("y")),
+            app(var("f"), abs("x", app(var("x"), var("y")))),
+            app(var("x"), app(var("y"), app(var("z"), var("w")))),
+            abs("x", app(app(abs("y", var("y")), var("x")), abs("z", var("z")))),
+            app(
+                app(var("a"), app(var("b"), abs("c", var("c")))),
+                abs("d", var("d")),
+            ),
+            // keywords used as names are escaped
+            abs("in", app(var("in"), var("let"))),
+        ];
+        for term in terms {
+            let printed = pretty_print(&term);
+            assert_eq!(parse(&printed), Ok(term.clone()), "round trip of `{printed}`");
+            let printed = pretty_print_with(&term, Notation::Ascii);
+            assert_eq!(parse(&printed), Ok(term), "round trip of `{printed}`");
+        }
+    }
+
+    #[test]
+    fn test_pretty_print_deep_term_is_stack_safe() {
+        let mut term = var("x");
+        for _ in 0..1_000_000 {
+            term = abs("x", app(var("x"), term));
+        }
+        let printed = pretty_print(&term);
+        assert!(printed.starts_with("λx. x λx. x λx."));
+        assert!(format!("{term:?}").starts_with("Term(λx. x λx."));
+    }
+
+    #[test]
+    fn test_pretty_print_ascii() {
+        let term = app(abs("x", abs("y", var("x"))), var("z"));
+        assert_eq!(pretty_print_with(&term, Notation::Ascii), "(\\x. \\y. x) z");
+        assert_eq!(format!("{term:#}"), "(\\x. \\y. x) z");
+    }
+}
@@ -0,0 +1,635 @@
+use crate::env::*;
+use crate::equiv::*;
+use crate::eval::*;
+use crate::forms::*;
+use crate::graph::*;
+use crate::parser::*;
+use crate::secd::*;
+use crate::term::*;
+use std::fs;
+use std::io::{self, Write};
+
+const HELP: &str = "\
+Enter a term to evaluate it, or `def name = term` to define a name.
+
+Commands:
+  :help               show this help
+  :quit               leave the interpreter (also Ctrl-D)
+  :load <file>        run the statements of a file
+  :defs               list all definitions
+  :strategy [name]    show or set the strategy: normal, applicative, cbn, cbv or head
+  :backend [name]     show or set the evaluator: substitution, krivine, cek or secd
+  :eta [on|off]       show or set whether results are η-reduced
+  :step               perform one reduction step on the last term
+  :redexes            list the redexes of the last term with their positions
+  :reduce <n>         contract redex number n of the last term
+  :graph [depth]      show the reduction graph of the last term and the paths of all strategies
+  :secd [steps]       compile the last term to SECD code and trace the machine registers
+  :eq <term> = <term> check whether two terms have the same normal form";
+
+/// Commands understood by [`Repl::handle`], used for tab completion.
+pub const COMMANDS: [&str; 13] = [
+    ":help",
+    ":quit",
+    ":load",
+    ":defs",
+    ":strategy",
+    ":backend",
+    ":eta",
+    ":step",
+    ":redexes",
+    ":reduce",
+    ":graph",
+    ":secd",
+    ":eq",
+];
+
+/// Whether the REPL should keep reading input after a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Quit,
+}
+
+/// The state of an interactive session: definitions, settings and the last term.
+#[derive(Debug, Clone, Default)]
+pub struct Repl {
+    env: Env,
+    strategy: Strategy,
+    backend: Backend,
+    limits: Limits,
+    /// Whether results are η-reduced, giving βη-normal forms.
+    eta: bool,
+    /// The last term that was entered and how many times `:step` reduced it.
+    last: Option<(Term, usize)>,
+}
+
+impl Repl {
+    /// Creates a session without definitions.
+    pub fn new(strategy: Strategy, backend: Backend, limits: Limits, eta: bool) -> Self {
+        Repl {
+            env: Env::new(),
+            strategy,
+            backend,
+            limits,
+            eta,
+            last: None,
+        }
+    }
+
+    /// Handles one line of input, i.e. a statement or a command starting with `:`,
+    /// writing all output to `out`.
+    pub fn handle(&mut self, input: &str, out: &mut impl Write) -> io::Result<Control> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(Control::Continue);
+        }
+        let Some(command) = input.strip_prefix(':') else {
+            self.statement(input, out)?;
+            return Ok(Control::Continue);
+        };
+
+        let (name, argument) = match command.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+        match name {
+            "q" | "quit" => return Ok(Control::Quit),
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "l" | "load" if !argument.is_empty() => self.load(argument, out)?,
+            "l" | "load" => writeln!(out, "Usage: :load <file>")?,
+            "d" | "defs" => self.defs(out)?,
+            "strategy" => self.set_strategy(argument, out)?,
+            "backend" => self.set_backend(argument, out)?,
+            "eta" => self.set_eta(argument, out)?,
+            "s" | "step" => self.step(out)?,
+            "redexes" => self.list_redexes(out)?,
+            "reduce" => self.reduce(argument, out)?,
+            "graph" => self.graph(argument, out)?,
+            "secd" => self.secd(argument, out)?,
+            "eq" => self.equal(argument, out)?,
+            _ => writeln!(out, "Unknown command :{}, see :help", name)?,
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Names of all definitions, in the order they were made.
+    pub fn names(&self) -> Vec<String> {
+        self.env.iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    fn statement(&mut self, input: &str, out: &mut impl Write) -> io::Result<()> {
+        match parse_statement(input) {
+            Ok(Statement::Def(name, t)) => {
+                self.env.define(name, &t);
+                writeln!(out, "Defined {}", name)
+            }
+            Ok(Statement::Term(t)) => {
+                writeln!(out, "Original term: {}", t)?;
+                let t = self.env.resolve(&t);
+                let result = self.evaluate(&t);
+                self.last = Some((t, 0));
+                match result {
+                    Ok(result) => writeln!(out, "Evaluated term: {} ({})", result, classify(&result)),
+                    Err(error) => writeln!(out, "Evaluation error: {}", error),
+                }
+            }
+            Err(error) => writeln!(out, "{}", error.render(input)),
+        }
+    }
+
+    /// Runs all statements of a file, keeping its definitions.
+    fn load(&mut self, path: &str, out: &mut impl Write) -> io::Result<()> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => return writeln!(out, "Cannot read {}: {}", path, error),
+        };
+        let statements = match parse_program(&source) {
+            Ok(statements) => statements,
+            Err(error) => return writeln!(out, "{}", error.render_file(&source, path)),
+        };
+
+        let mut defined = 0;
+        for statement in statements {
+            match statement {
+                Statement::Def(name, t) => {
+                    self.env.define(name, &t);
+                    defined += 1;
+                }
+                Statement::Term(t) => {
+                    let t = self.env.resolve(&t);
+                    match self.evaluate(&t) {
+                        Ok(result) => writeln!(out, "{}", result)?,
+                        Err(error) => writeln!(out, "Evaluation error: {}", error)?,
+                    }
+                }
+            }
+        }
+        writeln!(out, "Loaded {} definitions from {}", defined, path)
+    }
+
+    /// Evaluates a resolved term with the settings of the session.
+    fn evaluate(&self, t: &Term) -> Result<Term, EvalError> {
+        let result = eval_on(t, self.strategy, self.limits, self.backend)?;
+        Ok(if self.eta { eta_reduce(&result) } else { result })
+    }
+
+    fn defs(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut any = false;
+        for (name, t) in self.env.iter() {
+            writeln!(out, "{} = {}", name, t)?;
+            any = true;
+        }
+        if !any {
+            writeln!(out, "No definitions")?;
+        }
+        Ok(())
+    }
+
+    fn set_strategy(&mut self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        if argument.is_empty() {
+            return writeln!(out, "Strategy: {}", self.strategy);
+        }
+        match argument.parse() {
+            Ok(strategy) => {
+                self.strategy = strategy;
+                writeln!(out, "Strategy: {}", self.strategy)
+            }
+            Err(error) => writeln!(out, "{}", error),
+        }
+    }
+
+    fn set_backend(&mut self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        if argument.is_empty() {
+            return writeln!(out, "Back end: {}", self.backend);
+        }
+        match argument.parse() {
+            Ok(backend) => {
+                self.backend = backend;
+                writeln!(out, "Back end: {}", self.backend)
+            }
+            Err(error) => writeln!(out, "{}", error),
+        }
+    }
+
+    fn set_eta(&mut self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        match argument {
+            "" => {}
+            "on" => self.eta = true,
+            "off" => self.eta = false,
+            _ => return writeln!(out, "Usage: :eta [on|off]"),
+        }
+        writeln!(out, "η-reduction: {}", if self.eta { "on" } else { "off" })
+    }
+
+    /// Reduces the last term by one step with the current strategy.
+    fn step(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let Some((t, steps)) = &mut self.last else {
+            return writeln!(out, "No term to reduce, enter a term first");
+        };
+        match step(t, self.strategy) {
+            Some(next) => {
+                *t = next;
+                *steps += 1;
+                writeln!(out, "Step {}: {}", steps, t)
+            }
+            None => writeln!(out, "{} is in normal form for strategy {}", t, self.strategy),
+        }
+    }
+
+    /// Lists the redexes of the last term, numbered from 1 in normal order.
+    fn list_redexes(&self, out: &mut impl Write) -> io::Result<()> {
+        let Some((t, _)) = &self.last else {
+            return writeln!(out, "No term to reduce, enter a term first");
+        };
+        let paths = redexes(t);
+        if paths.is_empty() {
+            return writeln!(out, "{} contains no redex", t);
+        }
+        for (i, path) in paths.iter().enumerate() {
+            let redex = subterm_at(t, path).expect("redex exists");
+            writeln!(out, "{}: {} at {}", i + 1, redex, format_path(path))?;
+        }
+        Ok(())
+    }
+
+    /// Contracts the redex with the given number, as listed by `:redexes`.
+    fn reduce(&mut self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        let Some((t, steps)) = &mut self.last else {
+            return writeln!(out, "No term to reduce, enter a term first");
+        };
+        let Ok(number) = argument.parse::<usize>() else {
+            return writeln!(out, "Usage: :reduce <n>, see :redexes");
+        };
+        let paths = redexes(t);
+        let Some(path) = number.checked_sub(1).and_then(|i| paths.get(i)) else {
+            return writeln!(out, "No redex {}, the term has {} redexes", number, paths.len());
+        };
+        *t = reduce_at(t, path).expect("path points to a redex");
+        *steps += 1;
+        writeln!(out, "Step {}: {}", steps, t)
+    }
+
+    /// Prints the reduction graph of the last term, explored up to the given
+    /// depth, followed by the way each strategy takes through it.
+    fn graph(&self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        let Some((t, _)) = &self.last else {
+            return writeln!(out, "No term to reduce, enter a term first");
+        };
+        let mut limits = GraphLimits::default();
+        if !argument.is_empty() {
+            match argument.parse() {
+                Ok(depth) => limits.max_depth = depth,
+                Err(_) => return writeln!(out, "Usage: :graph [depth]"),
+            }
+        }
+
+        let graph = reduction_graph(t, limits);
+        write!(out, "{}", graph)?;
+        for strategy in Strategy::ALL {
+            let path = graph.strategy_path(strategy);
+            let steps: Vec<String> = path.iter().map(|i| i.to_string()).collect();
+            let cycle = path[..path.len() - 1].contains(&path[path.len() - 1]);
+            let note = if cycle { " (diverges)" } else { "" };
+            writeln!(out, "{}: {}{}", strategy, steps.join(" -> "), note)?;
+        }
+        Ok(())
+    }
+
+    /// Prints the SECD code of the last term and the registers of the machine
+    /// before each instruction, running at most the given number of instructions.
+    fn secd(&self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        let Some((t, _)) = &self.last else {
+            return writeln!(out, "No term to reduce, enter a term first");
+        };
+        let max_instructions = match argument {
+            "" => 100,
+            _ => match argument.parse() {
+                Ok(max_instructions) => max_instructions,
+                Err(_) => return writeln!(out, "Usage: :secd [steps]"),
+            },
+        };
+
+        let program = compile(t);
+        write!(out, "{}", program)?;
+        let mut machine = Machine::new(&program);
+        for i in 0..max_instructions {
+            writeln!(out, "{}: {}", i, machine)?;
+            if !machine.step() {
+                break;
+            }
+        }
+        match machine.result() {
+            Some(result) => writeln!(out, "Result: {}\nβ-reductions: {}", result, machine.steps()),
+            None => writeln!(out, "(stopped after {} instructions)", max_instructions),
+        }
+    }
+
+    /// Compares two terms, given as `t1 = t2`, up to β-equivalence,
+    /// or βη-equivalence if η-reduction is on.
+    fn equal(&self, argument: &str, out: &mut impl Write) -> io::Result<()> {
+        if argument.is_empty() {
+            return writeln!(out, "Usage: :eq <term> = <term>");
+        }
+        let (t1, t2) = match &parse_equation(argument) {
+            Ok((t1, t2)) => (self.env.resolve(t1), self.env.resolve(t2)),
+            Err(error) => return writeln!(out, "{}", error.render(argument)),
+        };
+        let equivalence = if self.eta {
+            beta_eta_eq(&t1, &t2, self.limits.max_steps)
+        } else {
+            beta_eq(&t1, &t2, self.limits.max_steps)
+        };
+        writeln!(out, "{}", equivalence)
+    }
+}
+
+/// Whether `input` is an unfinished statement that continues on the next line:
+/// it has unclosed parentheses or ends with a token that requires something after it,
+/// such as the `.` of a lambda or the `=` of a definition.
+pub fn is_incomplete(input: &str) -> bool {
+    if input.trim_start().starts_with(':') {
+        return false;
+    }
+
+    let mut depth = 0;
+    let mut code = String::new();
+    for line in input.lines() {
+        // comments run to the end of the line
+        let line = line.split("--").next().unwrap_or("");
+        for c in line.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        code.push_str(line);
+        code.push(' ');
+    }
+
+    let code = code.trim_end();
+    let last_word = code.rsplit(char::is_whitespace).next().unwrap_or("");
+    depth > 0
+        || [".", "->", "=>", "=", "λ", "\\"].iter().any(|token| code.ends_with(token))
+        || KEYWORDS.contains(&last_word)
+}
+
+/// Completes the word before the cursor position `pos` in `line`.
+/// A word starting with `:` at the beginning of the line completes to a command,
+/// any other word to one of the defined `names` or a keyword.
+/// Returns the start of the completed word and the candidates.
+pub fn complete(line: &str, pos: usize, names: &[String]) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+        .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .map_or(0, |i| i + before[i..].chars().next().map_or(1, char::len_utf8));
+    let word = &before[start..];
+
+    if start == 1 && before.starts_with(':') {
+        let candidates = COMMANDS
+            .iter()
+            .filter(|command| command[1..].starts_with(word))
+            .map(|command| command.to_string())
+            .collect();
+        return (0, candidates);
+    }
+    if word.is_empty() {
+        return (pos, Vec::new());
+    }
+
+    let mut candidates: Vec<String> = names
+        .iter()
+        .map(String::as_str)
+        .chain(KEYWORDS)
+        .filter(|name| name.starts_with(word))
+        .map(str::to_string)
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `lines` to a new session and returns everything it printed.
+    fn session(lines: &[&str]) -> String {
+        let mut repl = Repl::default();
+        let mut out = Vec::new();
+        for line in lines {
+            if repl.handle(line, &mut out).unwrap() == Control::Quit {
+                break;
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_repl_evaluates_statements() {
+        let output = session(&["def id = λx. x", "id y"]);
+        assert_eq!(output, "Defined id\nOriginal term: id y\nEvaluated term: y (normal form)\n");
+    }
+
+    #[test]
+    fn test_repl_reports_form_of_result() {
+        let output = session(&[":strategy cbn", "(λx. λy. x) ((λz. z) w)"]);
+        assert!(output.ends_with("Evaluated term: λy. (λz. z) w (weak head normal form)\n"));
+        let output = session(&[":strategy head", "(λx. λy. y x) ((λz. z) w)"]);
+        assert!(output.ends_with("Evaluated term: λy. y ((λz. z) w) (head normal form)\n"));
+    }
+
+    #[test]
+    fn test_repl_quit_stops_reading() {
+        let output = session(&[":quit", "x"]);
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_repl_defs() {
+        assert_eq!(session(&[":defs"]), "No definitions\n");
+        let output = session(&["def id = λx. x", "def k x y = x", ":defs"]);
+        assert!(output.ends_with("id = λx. x\nk = λx. λy. x\n"));
+    }
+
+    #[test]
+    fn test_repl_strategy() {
+        let output = session(&[":strategy cbn", ":strategy", ":strategy lazy"]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "Strategy: cbn");
+        assert_eq!(lines[1], "Strategy: cbn");
+        assert!(lines[2].starts_with("Unknown strategy: lazy"));
+    }
+
+    #[test]
+    fn test_repl_backend() {
+        let output = session(&[":backend krivine", "(λx. λy. x) a b", ":strategy cbv", "x", ":backend ssa"]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "Back end: krivine");
+        assert_eq!(lines[2], "Evaluated term: a (normal form)");
+        assert_eq!(
+            lines[5],
+            "Evaluation error: The krivine back end does not support the cbv strategy"
+        );
+        assert!(lines[6].starts_with("Unknown back end: ssa"));
+    }
+
+    #[test]
+    fn test_repl_eta() {
+        let output = session(&["λx. f x", ":eta on", "λx. f x", ":eta", ":eta maybe"]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[1], "Evaluated term: λx. f x (normal form)");
+        assert_eq!(lines[2], "η-reduction: on");
+        assert_eq!(lines[4], "Evaluated term: f (normal form)");
+        assert_eq!(lines[5], "η-reduction: on");
+        assert_eq!(lines[6], "Usage: :eta [on|off]");
+    }
+
+    #[test]
+    fn test_repl_redexes() {
+        let output = session(&["(λx. x) ((λy. y) z)", ":redexes", ":reduce 2", ":reduce 2", ":redexes"]);
+        let lines: Vec<_> = output.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "1: (λx. x) ((λy. y) z) at root",
+                "2: (λy. y) z at right",
+                "Step 1: (λx. x) z",
+                "No redex 2, the term has 1 redexes",
+                "1: (λx. x) z at root",
+            ]
+        );
+        assert_eq!(session(&["x", ":redexes"]).lines().nth(2), Some("x contains no redex"));
+    }
+
+    #[test]
+    fn test_repl_graph() {
+        let output = session(&["(λx. z) ((λx. x x) (λx. x x))", ":graph"]);
+        let lines: Vec<_> = output.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0: (λx. z) ((λx. x x) λx. x x)",
+                "1: z (normal form)",
+                "0 -> 1 at root",
+                "0 -> 0 at right",
+                "normal: 0 -> 1",
+                "applicative: 0 -> 0 (diverges)",
+                "cbn: 0 -> 1",
+                "cbv: 0 -> 0 (diverges)",
+                "head: 0 -> 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repl_secd() {
+        let output = session(&["(λx. x) y", ":secd"]);
+        let lines: Vec<_> = output.lines().skip(2).collect();
+        assert_eq!(lines[..6], ["main:", "  closure L1", "  free y", "  apply", "L1: λx", "  access 0"]);
+        assert_eq!(lines[7], "0: S = [], E = [], C = [closure L1, free y, apply], D = []");
+        assert_eq!(lines[12], "5: S = [y], E = [], C = [], D = []");
+        assert_eq!(lines[13..], ["Result: y", "β-reductions: 1"]);
+
+        let output = session(&["(λx. x x) (λx. x x)", ":secd 2", ":secd many"]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[lines.len() - 2], "(stopped after 2 instructions)");
+        assert_eq!(lines[lines.len() - 1], "Usage: :secd [steps]");
+    }
+
+    #[test]
+    fn test_repl_eq() {
+        let output = session(&[
+            "def id = λx. x",
+            ":eq id id = λy. y",
+            ":eq id = λx. λy. x",
+            ":eq (λx. x x) (λx. x x) = z",
+            ":eq",
+            ":eq λx. f x = f",
+            ":eta on",
+            ":eq λx. f x = f",
+            ":eq f x",
+        ]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[1], "Equal");
+        assert_eq!(lines[2], "Not equal");
+        assert!(lines[3].starts_with("Unknown"));
+        assert_eq!(lines[4], "Usage: :eq <term> = <term>");
+        assert_eq!(lines[5], "Not equal");
+        assert_eq!(lines[7], "Equal");
+        assert_eq!(lines[8], "error: Unexpected end of input");
+    }
+
+    #[test]
+    fn test_repl_step() {
+        let output = session(&["(λx. x) ((λy. y) z)", ":step", ":step", ":step"]);
+        let lines: Vec<_> = output.lines().skip(2).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Step 1: (λy. y) z",
+                "Step 2: z",
+                "z is in normal form for strategy normal"
+            ]
+        );
+        assert_eq!(
+            session(&[":step"]),
+            "No term to reduce, enter a term first\n"
+        );
+    }
+
+    #[test]
+    fn test_repl_load() {
+        let path = std::env::temp_dir().join("lc_repl_test_load.lc");
+        fs::write(&path, "def id = λx. x\ndef k x y = x\nk id z\n").unwrap();
+        let load = format!(":load {}", path.display());
+        let output = session(&[&load, "k a b"]);
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[0], "λx. x");
+        assert!(lines[1].starts_with("Loaded 2 definitions"));
+        assert_eq!(lines[3], "Evaluated term: a (normal form)");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_repl_unknown_command() {
+        assert_eq!(session(&[":frobnicate"]), "Unknown command :frobnicate, see :help\n");
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("(λx. x"));
+        assert!(is_incomplete("def id = λx."));
+        assert!(is_incomplete("def plus m n ="));
+        assert!(is_incomplete("let x = y in"));
+        assert!(is_incomplete("f (g -- (comment)\n  x"));
+        assert!(!is_incomplete("(λx. x) y"));
+        assert!(!is_incomplete("x) y"));
+        assert!(!is_incomplete(":load (file"));
+        assert!(!is_incomplete("x -- done."));
+    }
+
+    #[test]
+    fn test_complete_commands() {
+        let (start, candidates) = complete(":st", 3, &[]);
+        assert_eq!(start, 0);
+        assert_eq!(candidates, vec![":strategy", ":step"]);
+        assert_eq!(complete(":", 1, &[]).1.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn test_complete_names() {
+        let names = vec!["plus".to_string(), "pair".to_string(), "id".to_string()];
+        // `λ` takes two bytes
+        let (start, candidates) = complete("λx. pl x", 7, &names);
+        assert_eq!(start, 5);
+        assert_eq!(candidates, vec!["plus"]);
+
+        let (_, candidates) = complete("(p", 2, &names);
+        assert_eq!(candidates, vec!["pair", "plus"]);
+
+        let (_, candidates) = complete("le", 2, &names);
+        assert_eq!(candidates, vec!["let"]);
+    }
+}
@@ -0,0 +1,451 @@
+//! A compiler from terms to SECD code and the SECD virtual machine.
+//!
+//! A term is compiled into blocks of postfix code: variables and
+//! abstractions push a value, and `apply` pops an argument and a function.
+//! Every abstraction becomes a block of its own, ending with `return`.
+//! The machine evaluates the code with call by value using four registers:
+//! the stack of values (S), the environment of the current block (E), the
+//! code still to run (C) and the dump (D), which saves the other three
+//! registers while a function runs.
+
+use crate::debruijn::*;
+use crate::eval::*;
+use crate::machine::{self, Readback};
+use crate::term::*;
+use std::fmt;
+use std::mem;
+
+/// An instruction of the SECD machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// Push the value of the bound variable with the given de Bruijn index.
+    Access(usize),
+    /// Push a free variable, by its index among the free variables.
+    Free(usize),
+    /// Push a closure of the given block with the current environment.
+    Closure(usize),
+    /// Pop an argument and a function and apply the function.
+    Apply,
+    /// Leave the current block, keeping the value on top of the stack.
+    Return,
+}
+
+/// A compiled abstraction, or the main code of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// The name of the parameter, which the readback uses for the abstraction.
+    pub param: Symbol,
+    pub code: Vec<Instruction>,
+}
+
+/// A compiled term: the main code is block 0, abstractions are compiled
+/// into the following blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub blocks: Vec<Block>,
+    /// Names of the free variables of the term.
+    pub free: Vec<Symbol>,
+}
+
+/// Compiles a term into SECD code.
+///
+/// Example: `(λx. x) y` compiles to
+/// ```text
+/// main:
+///   closure L1
+///   free y
+///   apply
+/// L1: λx
+///   access 0
+///   return
+/// ```
+pub fn compile(term: &Term) -> Program {
+    enum Task<'a> {
+        Visit(&'a DbTerm, usize),
+        Emit(usize, Instruction),
+    }
+
+    let (db, free) = DbTerm::from_term(term);
+    let mut blocks = vec![Block {
+        param: Symbol::EMPTY,
+        code: Vec::new(),
+    }];
+    // each task knows its block and, for visits, the number of enclosing abstractions
+    let mut tasks = vec![(Task::Visit(&db, 0), 0)];
+    while let Some((task, block)) = tasks.pop() {
+        let (term, depth) = match task {
+            Task::Visit(term, depth) => (term, depth),
+            Task::Emit(block, instruction) => {
+                blocks[block].code.push(instruction);
+                continue;
+            }
+        };
+        match term {
+            DbTerm::Var(index) if *index < depth => blocks[block].code.push(Instruction::Access(*index)),
+            DbTerm::Var(index) => blocks[block].code.push(Instruction::Free(index - depth)),
+            DbTerm::Abs(param, body) => {
+                let inner = blocks.len();
+                blocks.push(Block {
+                    param: *param,
+                    code: Vec::new(),
+                });
+                blocks[block].code.push(Instruction::Closure(inner));
+                tasks.push((Task::Emit(inner, Instruction::Return), inner));
+                tasks.push((Task::Visit(body, depth + 1), inner));
+            }
+            DbTerm::App(t1, t2) => {
+                tasks.push((Task::Emit(block, Instruction::Apply), block));
+                tasks.push((Task::Visit(t2, depth), block));
+                tasks.push((Task::Visit(t1, depth), block));
+            }
+        }
+    }
+    Program { blocks, free }
+}
+
+impl Program {
+    /// Formats an instruction with the names of free variables and labels of blocks.
+    fn format_instruction(&self, instruction: Instruction) -> String {
+        match instruction {
+            Instruction::Access(index) => format!("access {}", index),
+            Instruction::Free(index) => format!("free {}", self.free[index]),
+            Instruction::Closure(block) => format!("closure {}", label(block)),
+            Instruction::Apply => "apply".to_string(),
+            Instruction::Return => "return".to_string(),
+        }
+    }
+}
+
+/// The label of a block in the disassembly.
+fn label(block: usize) -> String {
+    if block == 0 {
+        "main".to_string()
+    } else {
+        format!("L{}", block)
+    }
+}
+
+/// Disassembles the program: every block with its label, the parameter
+/// of abstractions and one instruction per line.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i == 0 {
+                writeln!(f, "main:")?;
+            } else {
+                writeln!(f, "{}: λ{}", label(i), block.param)?;
+            }
+            for &instruction in &block.code {
+                writeln!(f, "  {}", self.format_instruction(instruction))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+type Frames = machine::Frames<Value>;
+
+/// A value on the stack or in an environment.
+type Value = machine::Value<Closure>;
+
+/// A block with the environment it was created in.
+#[derive(Clone)]
+struct Closure(usize, Frames);
+
+/// The registers saved by `apply`.
+struct Dump {
+    stack: Vec<Value>,
+    env: Frames,
+    block: usize,
+    pc: usize,
+}
+
+/// The SECD machine running a program.
+///
+/// The machine halts when the main code is done, with the result on the stack.
+pub struct Machine<'p> {
+    program: &'p Program,
+    stack: Vec<Value>,
+    env: Frames,
+    /// The current block and the position of the next instruction in it.
+    block: usize,
+    pc: usize,
+    dump: Vec<Dump>,
+    steps: usize,
+}
+
+impl<'p> Machine<'p> {
+    /// Starts running the main code of `program`.
+    pub fn new(program: &'p Program) -> Self {
+        Machine {
+            program,
+            stack: Vec::new(),
+            env: Frames::default(),
+            block: 0,
+            pc: 0,
+            dump: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Number of functions applied so far, i.e. of β-reduction steps.
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.next().is_none()
+    }
+
+    fn next(&self) -> Option<Instruction> {
+        self.program.blocks[self.block].code.get(self.pc).copied()
+    }
+
+    /// Executes the next instruction. Returns `false` if the machine has halted.
+    pub fn step(&mut self) -> bool {
+        let Some(instruction) = self.next() else {
+            return false;
+        };
+        self.pc += 1;
+        match instruction {
+            Instruction::Access(index) => {
+                let value = self.env.get(index).expect("compiled code accesses bound variables only");
+                self.stack.push(value.clone());
+            }
+            Instruction::Free(head) => self.stack.push(Value::free(head)),
+            Instruction::Closure(block) => {
+                self.stack.push(Value::Closure(Closure(block, self.env.clone())))
+            }
+            Instruction::Apply => {
+                let arg = self.stack.pop().expect("missing argument");
+                match self.stack.pop().expect("missing function") {
+                    Value::Closure(Closure(block, env)) => {
+                        self.dump.push(Dump {
+                            stack: mem::take(&mut self.stack),
+                            env: mem::replace(&mut self.env, env.push(arg)),
+                            block: self.block,
+                            pc: self.pc,
+                        });
+                        self.block = block;
+                        self.pc = 0;
+                        self.steps += 1;
+                    }
+                    Value::Neutral(neutral) => self.stack.push(Value::apply_neutral(neutral, arg)),
+                    Value::Level(_) => unreachable!("levels are only used by the readback"),
+                }
+            }
+            Instruction::Return => {
+                let value = self.stack.pop().expect("missing return value");
+                let dump = self.dump.pop().expect("return outside of a function");
+                self.stack = dump.stack;
+                self.stack.push(value);
+                self.env = dump.env;
+                self.block = dump.block;
+                self.pc = dump.pc;
+            }
+        }
+        true
+    }
+
+    /// Whether the next instruction applies a closure, i.e. performs a β-step.
+    fn applies_closure(&self) -> bool {
+        self.next() == Some(Instruction::Apply)
+            && matches!(self.stack.iter().rev().nth(1), Some(Value::Closure(_)))
+    }
+
+    /// The result of the program, once the machine has halted.
+    pub fn result(&self) -> Option<Term> {
+        if !self.is_halted() {
+            return None;
+        }
+        let value = self.stack.last().expect("missing result").clone();
+        let mut readback = Readback::new();
+        readback.push(Quote::Value(value), 0);
+        self.readback(&mut readback);
+        Some(readback.finish().to_term(&self.program.free))
+    }
+
+    /// The term that the machine is computing the value of: the values on
+    /// the stack, followed by the code still to run, plugged into the
+    /// computations saved on the dump.
+    fn current_term(&self) -> Term {
+        let current = (&self.stack, &self.env, self.block, self.pc);
+        let saved = self
+            .dump
+            .iter()
+            .rev()
+            .map(|dump| (&dump.stack, &dump.env, dump.block, dump.pc));
+        let mut hole = None;
+        for (stack, env, block, pc) in std::iter::once(current).chain(saved) {
+            let mut readback = Readback::new();
+            for value in stack.iter().rev() {
+                readback.push(Quote::Value(value.clone()), 0);
+            }
+            self.readback(&mut readback);
+            if let Some(hole) = hole {
+                readback.result(hole);
+            }
+            let code = &self.program.blocks[block].code[pc..];
+            readback.push(Quote::Code(code, env.clone()), 0);
+            self.readback(&mut readback);
+            hole = Some(readback.finish());
+        }
+        hole.expect("missing result").to_term(&self.program.free)
+    }
+
+    /// Runs the readback, substituting values for variables and executing
+    /// code symbolically.
+    fn readback(&self, readback: &mut Readback<Quote<'p>>) {
+        let blocks = &self.program.blocks;
+        readback.run(|readback, quote, depth| match quote {
+            Quote::Code(code, env) => {
+                // pushed in reverse, so that the first instruction is run first
+                for instruction in code.iter().rev() {
+                    match *instruction {
+                        Instruction::Access(index) => {
+                            let value = env.get(index).expect("compiled code accesses bound variables only");
+                            readback.push(Quote::Value(value.clone()), depth);
+                        }
+                        Instruction::Free(index) => readback.push(Quote::Free(index), depth),
+                        Instruction::Closure(block) => {
+                            let env = env.push(Value::Level(depth));
+                            readback.push_abs(blocks[block].param, Quote::Code(&blocks[block].code, env), depth);
+                        }
+                        Instruction::Apply => readback.push_app(1, depth),
+                        Instruction::Return => {}
+                    }
+                }
+            }
+            Quote::Value(value) => {
+                if let Some(Closure(block, env)) = readback.value(value, depth, Quote::Value) {
+                    let env = env.push(Value::Level(depth));
+                    readback.push_abs(blocks[block].param, Quote::Code(&blocks[block].code, env), depth);
+                }
+            }
+            Quote::Free(index) => readback.free(index, depth),
+        });
+    }
+
+    /// Formats a value: closures by their block, other values as terms.
+    fn format_value(&self, value: &Value) -> String {
+        match value {
+            Value::Closure(Closure(block, _)) => format!("⟨{}⟩", label(*block)),
+            _ => {
+                let mut readback = Readback::new();
+                readback.push(Quote::Value(value.clone()), 0);
+                self.readback(&mut readback);
+                readback.finish().to_term(&self.program.free).to_string()
+            }
+        }
+    }
+}
+
+/// Something to read back, see [`Machine::readback`].
+enum Quote<'p> {
+    /// Runs code symbolically, turning it into one term per value it pushes.
+    Code(&'p [Instruction], Frames),
+    Value(Value),
+    Free(usize),
+}
+
+/// Shows the four registers on one line. The stack lists its top last, the
+/// environment the value of index 0 first, the code the instructions still
+/// to run in the current block and the dump where each saved block resumes.
+///
+/// Example: `S = [⟨L1⟩, y], E = [], C = [apply], D = []`
+impl fmt::Display for Machine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stack: Vec<String> = self.stack.iter().map(|value| self.format_value(value)).collect();
+        let mut env = Vec::new();
+        let mut index = 0;
+        while let Ok(value) = self.env.get(index) {
+            env.push(self.format_value(value));
+            index += 1;
+        }
+        let code: Vec<String> = self.program.blocks[self.block].code[self.pc..]
+            .iter()
+            .map(|&instruction| self.program.format_instruction(instruction))
+            .collect();
+        let dump: Vec<String> = self
+            .dump
+            .iter()
+            .map(|dump| format!("{}:{}", label(dump.block), dump.pc))
+            .collect();
+        write!(
+            f,
+            "S = [{}], E = [{}], C = [{}], D = [{}]",
+            stack.join(", "),
+            env.join(", "),
+            code.join(", "),
+            dump.join(", ")
+        )
+    }
+}
+
+/// Compiles a term and runs it on the SECD machine, see [`Backend::Secd`].
+///
+/// Supports [`Strategy::CallByValue`] only.
+pub fn eval_secd(term: &Term, strategy: Strategy, limits: Limits) -> Result<Term, EvalError> {
+    if strategy != Strategy::CallByValue {
+        return Err(EvalError::Unsupported {
+            backend: Backend::Secd,
+            strategy,
+        });
+    }
+    let program = compile(term);
+    let mut machine = Machine::new(&program);
+    loop {
+        if machine.steps() == limits.max_steps && machine.applies_closure() {
+            return machine::outcome(machine.current_term(), machine.steps(), true, limits);
+        }
+        if !machine.step() {
+            let result = machine.result().expect("machine has halted");
+            return machine::outcome(result, machine.steps(), false, limits);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_compile_and_disassemble() {
+        let program = compile(&parse("(λx. x) y").unwrap());
+        assert_eq!(
+            program.blocks[0].code,
+            vec![Instruction::Closure(1), Instruction::Free(0), Instruction::Apply]
+        );
+        assert_eq!(
+            program.to_string(),
+            "main:\n  closure L1\n  free y\n  apply\nL1: λx\n  access 0\n  return\n"
+        );
+
+        let program = compile(&parse("λx. λy. x").unwrap());
+        assert_eq!(program.blocks[2].code, vec![Instruction::Access(1), Instruction::Return]);
+    }
+
+    #[test]
+    fn test_machine_trace() {
+        let program = compile(&parse("(λx. x) y").unwrap());
+        let mut machine = Machine::new(&program);
+        let mut states = vec![machine.to_string()];
+        while machine.step() {
+            states.push(machine.to_string());
+        }
+        assert_eq!(
+            states,
+            [
+                "S = [], E = [], C = [closure L1, free y, apply], D = []",
+                "S = [⟨L1⟩], E = [], C = [free y, apply], D = []",
+                "S = [⟨L1⟩, y], E = [], C = [apply], D = []",
+                "S = [], E = [y], C = [access 0, return], D = [main:3]",
+                "S = [y], E = [y], C = [return], D = [main:3]",
+                "S = [y], E = [], C = [], D = []",
+            ]
+        );
+        assert_eq!(machine.result(), Some(var("y")));
+        assert_eq!(machine.steps(), 1);
+    }
+}
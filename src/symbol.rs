@@ -0,0 +1,158 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+/// An interned variable name.
+///
+/// A symbol is a small index into a global table of names, so copying,
+/// comparing and hashing it is as cheap as for an integer. Symbols are
+/// ordered by the time their name was first interned, not alphabetically.
+///
+/// Interned names live until the program ends. This includes the fresh
+/// names made by [`Symbol::numbered`] when renaming variables, which are
+/// kept few by reusing numbers instead of appending to earlier suffixes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The global table of names, indexed by symbol.
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(|| {
+    Mutex::new(Interner {
+        symbols: HashMap::from([("", Symbol::EMPTY)]),
+        names: vec![""],
+    })
+});
+
+thread_local! {
+    /// A copy of the names of the global table, so that looking up a name
+    /// only takes the lock if the symbol was interned after the last lookup
+    /// on this thread. Names are never removed, so the copy stays valid.
+    static NAMES: RefCell<Vec<&'static str>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Symbol {
+    /// The empty name, which is interned from the start.
+    pub const EMPTY: Symbol = Symbol(0);
+
+    /// The symbol for `name`, adding the name to the table if necessary.
+    pub fn intern(name: &str) -> Symbol {
+        let mut interner = INTERNER.lock().expect("interner poisoned");
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(interner.names.len()).expect("too many symbols"));
+        let name: &'static str = Box::leak(name.into());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The name of the symbol.
+    pub fn as_str(self) -> &'static str {
+        let index = self.0 as usize;
+        NAMES.with_borrow_mut(|names| {
+            if index >= names.len() {
+                let interner = INTERNER.lock().expect("interner poisoned");
+                names.extend_from_slice(&interner.names[names.len()..]);
+            }
+            names[index]
+        })
+    }
+
+    /// The name `stem_n`, where `stem` is the name of the symbol without a
+    /// numeric suffix `_123`, used to make fresh names.
+    ///
+    /// Replacing the suffix instead of appending another one means that
+    /// renaming variables repeatedly does not make up ever longer names like
+    /// `x_1_1_1`. As the evaluators start numbering at 1 for every
+    /// substitution, they keep reusing the same few interned names.
+    ///
+    /// Example: both `x` and `x_2` numbered `3` give `x_3`.
+    pub fn numbered(self, n: usize) -> Symbol {
+        let name = self.as_str();
+        let stem = match name.rsplit_once('_') {
+            Some((stem, digits)) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => stem,
+            _ => name,
+        };
+        Symbol::intern(&format!("{}_{}", stem, n))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning_is_idempotent() {
+        let x = Symbol::intern("x");
+        assert_eq!(x, Symbol::intern("x"));
+        assert_ne!(x, Symbol::intern("y"));
+        assert_eq!(x.as_str(), "x");
+        assert_eq!(Symbol::intern(""), Symbol::EMPTY);
+    }
+
+    #[test]
+    fn test_numbered() {
+        let x = Symbol::intern("x");
+        assert_eq!(x.numbered(1), "x_1");
+        assert_eq!(x.numbered(1).numbered(2), "x_2");
+        assert_eq!(Symbol::intern("a_b").numbered(3), "a_b_3");
+        assert_eq!(Symbol::intern("y_").numbered(1), "y__1");
+    }
+
+    #[test]
+    fn test_as_str_on_other_threads() {
+        let z = Symbol::intern("z");
+        let name = std::thread::spawn(move || {
+            let w = Symbol::intern("w");
+            (z.as_str(), w)
+        })
+        .join()
+        .unwrap();
+        assert_eq!(name.0, "z");
+        assert_eq!(name.1.as_str(), "w");
+    }
+
+    #[test]
+    fn test_symbol_formatting() {
+        let symbol = Symbol::from("x_1");
+        assert_eq!(symbol.to_string(), "x_1");
+        assert_eq!(format!("{symbol:?}"), "\"x_1\"");
+        assert!(symbol == "x_1");
+    }
+}
@@ -1,18 +1,195 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+pub use crate::symbol::Symbol;
+use std::mem;
+
+/// A lambda term. Variable names are interned [`Symbol`]s.
+///
+/// `Clone`, `PartialEq` and `Drop` are implemented with an explicit stack
+/// instead of recursion, so that terms with millions of nodes can be handled
+/// without overflowing the native stack. `Debug` uses the iterative printer.
+#[derive(Eq)]
 pub enum Term {
-    Var(String),
-    Abs(String, Box<Term>),
+    Var(Symbol),
+    Abs(Symbol, Box<Term>),
     App(Box<Term>, Box<Term>),
 }
 
+impl Term {
+    /// Number of nodes (variables, abstractions and applications) in the term.
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        let mut stack = vec![self];
+        while let Some(term) = stack.pop() {
+            size += 1;
+            match term {
+                Term::Var(_) => {}
+                Term::Abs(_, body) => stack.push(body),
+                Term::App(t1, t2) => {
+                    stack.push(t1);
+                    stack.push(t2);
+                }
+            }
+        }
+        size
+    }
+}
+
+impl Clone for Term {
+    fn clone(&self) -> Self {
+        struct Rebuild;
+
+        impl<'a> Fold<&'a Term> for Rebuild {
+            type Binder = Symbol;
+            type Output = Term;
+
+            fn visit(&mut self, term: &'a Term) -> Visit<&'a Term, Symbol, Term> {
+                Visit::subterms(term)
+            }
+
+            fn abs(&mut self, param: Symbol, body: Term) -> Term {
+                Term::Abs(param, Box::new(body))
+            }
+
+            fn app(&mut self, t1: Term, t2: Term) -> Term {
+                Term::App(Box::new(t1), Box::new(t2))
+            }
+        }
+
+        fold(self, &mut Rebuild)
+    }
+}
+
+impl PartialEq for Term {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Term::Var(x), Term::Var(y)) if x == y => {}
+                (Term::Abs(x, body1), Term::Abs(y, body2)) if x == y => stack.push((body1, body2)),
+                (Term::App(t1, t2), Term::App(u1, u2)) => {
+                    stack.push((t2, u2));
+                    stack.push((t1, u1));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Drop for Term {
+    fn drop(&mut self) {
+        // Detach the children before they are dropped, so that dropping
+        // never recurses deeper than one level.
+        let mut stack = Vec::new();
+        take_children(self, &mut stack);
+        while let Some(mut term) = stack.pop() {
+            take_children(&mut term, &mut stack);
+        }
+    }
+}
+
+/// Moves the children of `term` to `stack`, leaving cheap placeholders behind,
+/// so `term` should be dropped right after. The children of an application
+/// are pushed function first.
+pub(crate) fn take_children(term: &mut Term, stack: &mut Vec<Term>) {
+    match term {
+        Term::Var(_) => {}
+        Term::Abs(_, body) => stack.push(mem::replace(&mut **body, Term::Var(Symbol::EMPTY))),
+        Term::App(t1, t2) => {
+            stack.push(mem::replace(&mut **t1, Term::Var(Symbol::EMPTY)));
+            stack.push(mem::replace(&mut **t2, Term::Var(Symbol::EMPTY)));
+        }
+    }
+}
+
+/// A computation over a tree-shaped term `T`, such as a [`Term`] or a
+/// [`crate::debruijn::DbTerm`], that builds its result bottom-up. Run by
+/// [`fold`], which keeps the pending subterms on an explicit stack, so that
+/// the depth of the term is not limited by the native stack.
+pub trait Fold<T> {
+    /// What [`Fold::visit`] passes on to [`Fold::abs`] for an abstraction,
+    /// usually the name of its parameter.
+    type Binder;
+    type Output;
+
+    /// Looks at a term on the way down, before its subterms.
+    fn visit(&mut self, term: T) -> Visit<T, Self::Binder, Self::Output>;
+
+    /// Builds the result for an abstraction, once its body is done.
+    fn abs(&mut self, binder: Self::Binder, body: Self::Output) -> Self::Output;
+
+    /// Builds the result for an application, once both sides are done.
+    fn app(&mut self, t1: Self::Output, t2: Self::Output) -> Self::Output;
+}
+
+/// How [`fold`] continues with a term, as decided by [`Fold::visit`].
+pub enum Visit<T, B, R> {
+    /// The result for the term, without visiting its subterms.
+    Done(R),
+    /// An abstraction: the body is visited, then [`Fold::abs`] is called.
+    Abs(B, T),
+    /// An application: both sides are visited from left to right, then
+    /// [`Fold::app`] is called.
+    App(T, T),
+}
+
+impl<'a> Visit<&'a Term, Symbol, Term> {
+    /// Visits the subterms of `term`, and copies a variable.
+    pub fn subterms(term: &'a Term) -> Self {
+        match term {
+            Term::Var(x) => Visit::Done(Term::Var(*x)),
+            Term::Abs(param, body) => Visit::Abs(*param, body),
+            Term::App(t1, t2) => Visit::App(t1, t2),
+        }
+    }
+}
+
+/// Runs `folder` on `term`, see [`Fold`].
+pub fn fold<T, F: Fold<T>>(term: T, folder: &mut F) -> F::Output {
+    enum Task<T, B> {
+        Visit(T),
+        BuildAbs(B),
+        BuildApp,
+    }
+
+    let mut tasks = vec![Task::Visit(term)];
+    let mut results = Vec::new();
+    while let Some(task) = tasks.pop() {
+        match task {
+            Task::Visit(term) => match folder.visit(term) {
+                Visit::Done(result) => results.push(result),
+                Visit::Abs(binder, body) => {
+                    tasks.push(Task::BuildAbs(binder));
+                    tasks.push(Task::Visit(body));
+                }
+                Visit::App(t1, t2) => {
+                    tasks.push(Task::BuildApp);
+                    tasks.push(Task::Visit(t2));
+                    tasks.push(Task::Visit(t1));
+                }
+            },
+            Task::BuildAbs(binder) => {
+                let body = results.pop().expect("missing body");
+                results.push(folder.abs(binder, body));
+            }
+            Task::BuildApp => {
+                let t2 = results.pop().expect("missing argument");
+                let t1 = results.pop().expect("missing function");
+                results.push(folder.app(t1, t2));
+            }
+        }
+    }
+    results.pop().expect("missing result")
+}
+
 /// Helper function to create a variable term.
 pub fn var(name: &str) -> Term {
-    Term::Var(name.to_string())
+    Term::Var(Symbol::intern(name))
 }
 
 /// Helper function to create an abstraction term.
 pub fn abs(param: &str, body: Term) -> Term {
-    Term::Abs(param.to_string(), Box::new(body))
+    Term::Abs(Symbol::intern(param), Box::new(body))
 }
 
 /// Helper function to create an application term.